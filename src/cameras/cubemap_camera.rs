@@ -0,0 +1,102 @@
+use crate::ray_tracer::{interface::camera_base::Camera, utils::Ray};
+use crate::utils::vec4::{Point, Vec4};
+
+// Renders six 90° faces into a 3x2 grid, the image should have an aspect ratio of 3:2.
+// Faces are relative to the camera frame (forward is towards look_at):
+//
+//  +-------+---------+-------+
+//  | left  | forward | right |
+//  +-------+---------+-------+
+//  | back  |   up    | down  |
+//  +-------+---------+-------+
+pub struct CubemapCamera {
+    origin: Point,
+    // (forward, right, up) of each face in the order of the layout, top row first
+    faces: [(Vec4, Vec4, Vec4); 6],
+}
+
+impl CubemapCamera {
+    pub fn new(look_from: Point, look_at: Point, view_up: Vec4) -> Self {
+        // local camera direction vectors
+        let local_z = (look_from - look_at).normalise();
+        let local_x = view_up.cross(local_z).normalise();
+        let local_y = local_z.cross(local_x);
+
+        Self {
+            origin: look_from,
+            faces: [
+                (-local_x, -local_z, local_y), // left
+                (-local_z, local_x, local_y),  // forward
+                (local_x, local_z, local_y),   // right
+                (local_z, -local_x, local_y),  // back
+                (local_y, local_x, local_z),   // up
+                (-local_y, local_x, -local_z), // down
+            ],
+        }
+    }
+}
+
+impl Camera for CubemapCamera {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
+        let column = ((u * 3.) as usize).min(2);
+        let row = (((1. - v) * 2.) as usize).min(1); // top row first
+        let (forward, right, up) = self.faces[row * 3 + column];
+
+        // co-ordinates on the face, domain => [-1, 1]
+        let s = (u * 3. - column as f32) * 2. - 1.;
+        let t = ((v * 2.) - (1 - row) as f32) * 2. - 1.;
+
+        Some(Ray::new(
+            self.origin,
+            (forward + right * s + up * t).normalise(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_centers_look_along_the_axes() {
+        let camera = CubemapCamera::new(
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        );
+
+        for ((u, v), expected) in [
+            ((1. / 6., 0.75), Vec4::new(-1., 0., 0., 0.)), // left
+            ((0.5, 0.75), Vec4::new(0., 0., -1., 0.)),     // forward
+            ((5. / 6., 0.75), Vec4::new(1., 0., 0., 0.)),  // right
+            ((1. / 6., 0.25), Vec4::new(0., 0., 1., 0.)),  // back
+            ((0.5, 0.25), Vec4::new(0., 1., 0., 0.)),      // up
+            ((5. / 6., 0.25), Vec4::new(0., -1., 0., 0.)), // down
+        ] {
+            let ray = camera.generate_ray(u, v).unwrap();
+            assert!(
+                (ray.direction - expected).length() < 1e-5,
+                "({}, {}): {:?}",
+                u,
+                v,
+                ray.direction.e
+            );
+        }
+    }
+
+    /// Neighbouring faces of the top row share their edges
+    #[test]
+    fn faces_meet_at_the_seams() {
+        let camera = CubemapCamera::new(
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        );
+
+        for (u, v) in [(1. / 3., 0.6), (2. / 3., 0.9)] {
+            let left = camera.generate_ray(u - 1e-4, v).unwrap();
+            let right = camera.generate_ray(u + 1e-4, v).unwrap();
+            assert!((left.direction - right.direction).length() < 1e-2);
+        }
+    }
+}
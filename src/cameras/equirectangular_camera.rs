@@ -0,0 +1,122 @@
+use super::Eye;
+use crate::ray_tracer::{interface::camera_base::Camera, utils::Ray};
+use crate::utils::vec4::{Point, Vec4};
+use std::f32::consts::PI;
+
+// Full 360° x 180° latitude-longitude panorama, the image should have an aspect ratio of 2:1.
+// The center of the image looks towards look_at.
+pub struct EquirectangularCamera {
+    origin: Point,
+    local_x: Vec4,
+    local_y: Vec4,
+    local_z: Vec4,
+
+    // omni-directional stereo, signed distance of the eye from origin (negative is left eye)
+    eye_offset: f32,
+    convergence_distance: f32,
+}
+
+impl EquirectangularCamera {
+    pub fn new(look_from: Point, look_at: Point, view_up: Vec4) -> Self {
+        // local camera direction vectors
+        let local_z = (look_from - look_at).normalise();
+        let local_x = view_up.cross(local_z).normalise();
+        let local_y = local_z.cross(local_x);
+
+        Self {
+            origin: look_from,
+            local_x,
+            local_y,
+            local_z,
+            eye_offset: 0.,
+            convergence_distance: f32::INFINITY,
+        }
+    }
+
+    /**
+    Turns the camera into one eye of an omni-directional stereo (ODS) pair. Every ray starts on a
+    circle of diameter `interocular_distance` around origin, tangent to the viewing direction, so
+    the stereo effect is correct for every longitude.
+
+    - `interocular_distance`: distance between the eyes in world units
+    - `convergence_distance`: distance of zero parallax, `f32::INFINITY` for parallel eyes
+    */
+    pub fn with_eye(
+        mut self,
+        eye: Eye,
+        interocular_distance: f32,
+        convergence_distance: f32,
+    ) -> Self {
+        self.eye_offset = match eye {
+            Eye::Left => -interocular_distance / 2.,
+            Eye::Right => interocular_distance / 2.,
+        };
+        self.convergence_distance = convergence_distance;
+        self
+    }
+
+    /// Maps u, v to a direction. u => longitude in [-pi, pi], v => latitude in [-pi/2, pi/2]
+    pub fn direction(&self, u: f32, v: f32) -> Vec4 {
+        let phi = (u - 0.5) * 2. * PI;
+        let theta = (v - 0.5) * PI;
+
+        (self.local_x * phi.sin() - self.local_z * phi.cos()) * theta.cos() +
+            self.local_y * theta.sin()
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
+        let direction = self.direction(u, v);
+        if self.eye_offset == 0. {
+            return Some(Ray::new(self.origin, direction));
+        }
+
+        // the eye sits to the side of the viewing direction, in the horizontal plane
+        let phi = (u - 0.5) * 2. * PI;
+        let side = self.local_x * phi.cos() + self.local_z * phi.sin();
+        let eye_origin = self.origin + side * self.eye_offset;
+
+        // toe in so both eyes meet at the convergence distance
+        let direction = if self.convergence_distance.is_finite() {
+            (direction * self.convergence_distance - side * self.eye_offset).normalise()
+        } else {
+            direction
+        };
+
+        Some(Ray::new(eye_origin, direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_longitude_and_latitude() {
+        let camera = EquirectangularCamera::new(
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        );
+
+        for ((u, v), expected) in [
+            ((0.5, 0.5), Vec4::new(0., 0., -1., 0.)), // look_at
+            ((0.75, 0.5), Vec4::new(1., 0., 0., 0.)), // right
+            ((0.25, 0.5), Vec4::new(-1., 0., 0., 0.)),
+            ((0., 0.5), Vec4::new(0., 0., 1., 0.)), // behind, on the seam
+            ((0.5, 1.), Vec4::new(0., 1., 0., 0.)), // up
+            ((0.5, 0.), Vec4::new(0., -1., 0., 0.)),
+        ] {
+            let ray = camera.generate_ray(u, v).unwrap();
+            assert!((ray.origin - Point::new(0., 0., 0., 0.)).length() < 1e-5);
+            assert!(
+                (ray.direction - expected).length() < 1e-5,
+                "({}, {}): {:?}",
+                u,
+                v,
+                ray.direction.e
+            );
+        }
+    }
+}
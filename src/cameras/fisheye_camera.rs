@@ -0,0 +1,128 @@
+use crate::ray_tracer::{interface::camera_base::Camera, utils::Ray};
+use crate::utils::vec4::{Point, Vec4};
+
+/// How the angle from the optical axis maps to the distance from the image center
+#[derive(Clone, Copy)]
+pub enum FisheyeProjection {
+    /// r = f * theta
+    Equidistant,
+    /// r = 2f * sin(theta / 2), preserves solid angle
+    Equisolid,
+}
+
+// The image circle is inscribed in the image height. Pixels outside of it generate no rays.
+pub struct FisheyeCamera {
+    projection: FisheyeProjection,
+    aspect_ratio: f32,
+    half_fov: f32, // in radians
+
+    origin: Point,
+    local_x: Vec4,
+    local_y: Vec4,
+    local_z: Vec4,
+}
+
+impl FisheyeCamera {
+    // fov in degrees, can go up to 360
+    pub fn new(
+        projection: FisheyeProjection,
+        aspect_ratio: f32,
+        fov: f32,
+        look_from: Point,
+        look_at: Point,
+        view_up: Vec4, // used to change roll of camera
+    ) -> Self {
+        // local camera direction vectors
+        let local_z = (look_from - look_at).normalise();
+        let local_x = view_up.cross(local_z).normalise();
+        let local_y = local_z.cross(local_x);
+
+        Self {
+            projection,
+            aspect_ratio,
+            half_fov: std::f32::consts::PI / 180.0 * fov / 2.,
+            origin: look_from,
+            local_x,
+            local_y,
+            local_z,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
+        // co-ordinates relative to the image circle, domain => [-1, 1]
+        let x = (u - 0.5) * 2. * self.aspect_ratio;
+        let y = (v - 0.5) * 2.;
+        let r = (x * x + y * y).sqrt();
+        if r > 1. {
+            return None;
+        }
+
+        // angle from the optical axis
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => r * self.half_fov,
+            FisheyeProjection::Equisolid => 2. * (r * (self.half_fov / 2.).sin()).asin(),
+        };
+
+        let (cos_phi, sin_phi) = if r > 0. { (x / r, y / r) } else { (1., 0.) };
+        let direction = (self.local_x * cos_phi + self.local_y * sin_phi) * theta.sin() -
+            self.local_z * theta.cos();
+
+        Some(Ray::new(self.origin, direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn camera(projection: FisheyeProjection) -> FisheyeCamera {
+        FisheyeCamera::new(
+            projection,
+            1.,
+            180.,
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        )
+    }
+
+    #[test]
+    fn edge_of_the_image_circle_is_at_half_the_fov() {
+        for projection in [FisheyeProjection::Equidistant, FisheyeProjection::Equisolid] {
+            let camera = camera(projection);
+
+            let ray = camera.generate_ray(0.5, 0.5).unwrap();
+            assert!((ray.direction - Vec4::new(0., 0., -1., 0.)).length() < 1e-5);
+
+            // 180° fov, so the top and right edges look 90° away from the optical axis
+            let ray = camera.generate_ray(0.5, 1.).unwrap();
+            assert!((ray.direction - Vec4::new(0., 1., 0., 0.)).length() < 1e-5);
+            let ray = camera.generate_ray(1., 0.5).unwrap();
+            assert!((ray.direction - Vec4::new(1., 0., 0., 0.)).length() < 1e-5);
+
+            // corners are outside of the image circle
+            assert!(camera.generate_ray(0., 0.).is_none());
+        }
+    }
+
+    /// Halfway to the edge, equidistant is at half the angle and equisolid at half the chord
+    #[test]
+    fn projections_map_radius_to_angle() {
+        let cos_theta = |projection| {
+            -camera(projection)
+                .generate_ray(0.75, 0.5)
+                .unwrap()
+                .direction
+                .z()
+        };
+        let equidistant = cos_theta(FisheyeProjection::Equidistant);
+        let equisolid = cos_theta(FisheyeProjection::Equisolid);
+        assert!((equidistant - FRAC_PI_4.cos()).abs() < 1e-5);
+        // 2 sin(theta / 2) = 0.5 * 2 sin(45°)
+        let theta = 2. * (0.5 * FRAC_PI_4.sin()).asin();
+        assert!((equisolid - theta.cos()).abs() < 1e-5);
+    }
+}
@@ -1 +1,14 @@
-pub mod perspective_camera;
\ No newline at end of file
+pub mod cubemap_camera;
+pub mod equirectangular_camera;
+pub mod fisheye_camera;
+pub mod orthographic_camera;
+pub mod perspective_camera;
+pub mod realistic_camera;
+pub mod stereo_camera;
+
+/// Which eye a stereo view is rendered for, shared by the stereo rig and the ODS projection
+#[derive(Clone, Copy)]
+pub enum Eye {
+    Left,
+    Right,
+}
\ No newline at end of file
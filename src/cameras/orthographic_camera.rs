@@ -0,0 +1,81 @@
+use crate::ray_tracer::{interface::camera_base::Camera, utils::Ray};
+use crate::utils::vec4::{Point, Vec4};
+
+// All rays share the same direction, so there is no perspective foreshortening.
+// The view width and height are in world units (not angles) and set the extent of the image plane.
+pub struct OrthographicCamera {
+    direction: Vec4,
+    lower_left_corner: Point,
+    horizontal_offset_vec: Point, // offset vector from lower_left_corner to reach right edge
+    vertical_offset_vec: Point,   // offset vector from lower_left_corner to reach top edge
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        view_width: f32,
+        view_height: f32,
+        look_from: Point,
+        look_at: Point,
+        view_up: Vec4, // used to change roll of camera
+    ) -> Self {
+        // local camera direction vectors
+        let local_z = (look_from - look_at).normalise();
+        let local_x = view_up.cross(local_z).normalise();
+        let local_y = local_z.cross(local_x);
+
+        let horizontal_offset_vec = local_x * view_width;
+        let vertical_offset_vec = local_y * view_height;
+
+        Self {
+            direction: -local_z,
+            horizontal_offset_vec,
+            vertical_offset_vec,
+
+            // the image plane passes through look_from, centered on it
+            lower_left_corner: look_from -
+                (horizontal_offset_vec / 2.) -
+                (vertical_offset_vec / 2.),
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
+        let origin = self.lower_left_corner +
+            (self.horizontal_offset_vec * u) +
+            (self.vertical_offset_vec * v);
+
+        Some(Ray::new(origin, self.direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rays_are_parallel_and_start_on_the_image_plane() {
+        let look_from = Point::new(1., 2., 3., 0.);
+        let look_at = Point::new(1., 2., -1., 0.);
+        let camera = OrthographicCamera::new(
+            4.,
+            2.,
+            look_from,
+            look_at,
+            Vec4::new(0., 1., 0., 0.),
+        );
+        let forward = Vec4::new(0., 0., -1., 0.);
+
+        // the center ray starts at look_from and points at look_at
+        let ray = camera.generate_ray(0.5, 0.5).unwrap();
+        assert!((ray.origin - look_from).length() < 1e-5);
+        assert!((ray.direction - forward).length() < 1e-5);
+
+        // corners are half the view width and height away, with the same direction
+        let ray = camera.generate_ray(0., 1.).unwrap();
+        assert!((ray.origin - Point::new(-1., 3., 3., 0.)).length() < 1e-5);
+        assert!((ray.direction - forward).length() < 1e-5);
+        let ray = camera.generate_ray(1., 0.).unwrap();
+        assert!((ray.origin - Point::new(3., 1., 3., 0.)).length() < 1e-5);
+    }
+}
@@ -3,12 +3,6 @@ use crate::utils::vec4::{Point, Vec4};
 
 // The size of the virtual viewport if important since it is relative to the virtual world
 pub struct PerspectiveCamera {
-    focal_length: f32,
-    aspect_ratio: f32,
-    // * This is the virtual viewport (the image plane)
-    viewport_height: f32,
-    viewport_width: f32,
-
     origin: Point,
     lower_left_corner: Point,
     horizontal_offset_vec: Point, // offset vector from lower_left_corner to reach right edge
@@ -30,6 +24,7 @@ impl PerspectiveCamera {
         let local_x = view_up.cross(local_z).normalise();
         let local_y = local_z.cross(local_x);
 
+        // * This is the virtual viewport (the image plane)
         let theta = std::f32::consts::PI / 180.0 * vfov;
         let viewport_height = 2.0 * (theta / 2.0).tan();
         let viewport_width = aspect_ratio * viewport_height;
@@ -39,11 +34,6 @@ impl PerspectiveCamera {
         let vertical_offset_vec = local_y * viewport_height;
 
         Self {
-            focal_length,
-            aspect_ratio,
-            viewport_height,
-            viewport_width,
-
             // * Static origin as of now
             origin: look_from,
             horizontal_offset_vec,
@@ -56,16 +46,22 @@ impl PerspectiveCamera {
                 (vertical_offset_vec / 2.),
         }
     }
+
+    /// Shifts the image plane without rotating the camera (off-axis projection), `shift` is in world units
+    pub fn with_lens_shift(mut self, shift: Vec4) -> Self {
+        self.lower_left_corner += shift;
+        self
+    }
 }
 
 impl Camera for PerspectiveCamera {
-    fn generate_ray(&self, u: f32, v: f32) -> Ray {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
         let direction = (self.lower_left_corner +
             (self.horizontal_offset_vec * u) +
             (self.vertical_offset_vec * v) -
             self.origin)
             .normalise();
 
-        Ray::new(self.origin, direction)
+        Some(Ray::new(self.origin, direction))
     }
 }
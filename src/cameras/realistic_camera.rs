@@ -0,0 +1,438 @@
+use crate::materials::dielectric::Dielectric;
+use crate::ray_tracer::{interface::camera_base::Camera, utils::Ray};
+use crate::utils::vec4::{Point, Vec4};
+use rand::prelude::*;
+
+/// Double Gauss 50mm f/2 (US patent 2,673,491), values in mm
+pub const DOUBLE_GAUSS_50MM: &str = "
+# radius   thickness  ior    aperture
+29.475     3.76       1.67   25.2
+84.83      0.12       1      25.2
+19.275     4.025      1.67   23
+40.77      3.275      1.699  23
+12.75      5.705      1      18
+0          4.5        0      17.1
+-14.495    1.18       1.603  17
+40.77      6.065      1.658  20
+-20.385    0.19       1      20
+437.065    3.22       1.717  20
+-39.73     0          1      20
+";
+
+/// A single refracting surface (or the aperture stop) of a lens
+#[derive(Clone, Copy)]
+pub struct LensElement {
+    /// radius of curvature, positive if the center is towards the film, 0 for the aperture stop
+    pub radius: f32,
+    /// distance along the axis to the next element (towards the film)
+    pub thickness: f32,
+    /// refractive index of the medium after this surface (towards the film), 0 or 1 for air
+    pub refractive_index: f32,
+    /// diameter of the element
+    pub aperture: f32,
+}
+
+impl LensElement {
+    /**
+    Parses a lens prescription table, one element per line from the front (scene side) to the rear
+    (film side). Columns are radius, thickness, refractive index and aperture diameter, separated
+    by whitespace. Empty lines and lines starting with `#` are ignored.
+    */
+    pub fn parse_prescription(table: &str) -> Result<Vec<LensElement>, String> {
+        let mut elements = vec![];
+        for (line_number, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|e| format!("line {}: {}", line_number + 1, e))?;
+            if values.len() != 4 {
+                return Err(format!(
+                    "line {}: expected 4 columns, found {}",
+                    line_number + 1,
+                    values.len()
+                ));
+            }
+
+            elements.push(LensElement {
+                radius: values[0],
+                thickness: values[1],
+                refractive_index: values[2],
+                aperture: values[3],
+            });
+        }
+
+        if elements.is_empty() {
+            return Err(String::from("prescription has no elements"));
+        }
+        Ok(elements)
+    }
+
+    fn medium_index(&self) -> f32 {
+        if self.refractive_index == 0. {
+            1.
+        } else {
+            self.refractive_index
+        }
+    }
+}
+
+// Traces rays from the film through every lens element, so vignetting, distortion and the bokeh
+// shape come from the lens itself. Rays blocked by the lens housing carry no light.
+//
+// Lens space: the optical axis is z, pointing towards the scene, and the film sits at z = 0.
+pub struct RealisticCamera {
+    elements: Vec<LensElement>,
+    element_z: Vec<f32>, // axial position of the vertex of every element
+    film_width: f32,
+    film_height: f32,
+    scale: f32, // lens units to world units
+
+    origin: Point,
+    local_x: Vec4,
+    local_y: Vec4,
+    local_z: Vec4,
+}
+
+impl RealisticCamera {
+    /**
+    - `elements`: lens elements from front to rear, see [`LensElement::parse_prescription`]
+    - `film_diagonal`: diagonal of the film, in lens units (eg: 43.3 for 35mm film in mm)
+    - `focus_distance`: distance from the front of the lens that is in focus, in world units
+    - `scale`: size of one lens unit in world units (eg: 0.001 for a lens in mm and a scene in m)
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        elements: Vec<LensElement>,
+        aspect_ratio: f32,
+        film_diagonal: f32,
+        focus_distance: f32,
+        scale: f32,
+        look_from: Point,
+        look_at: Point,
+        view_up: Vec4, // used to change roll of camera
+    ) -> Self {
+        // local camera direction vectors
+        let local_z = (look_from - look_at).normalise();
+        let local_x = view_up.cross(local_z).normalise();
+        let local_y = local_z.cross(local_x);
+
+        let film_height = film_diagonal / (1. + aspect_ratio * aspect_ratio).sqrt();
+
+        let mut camera = Self {
+            elements,
+            element_z: vec![],
+            film_width: film_height * aspect_ratio,
+            film_height,
+            scale,
+            origin: look_from,
+            local_x,
+            local_y,
+            local_z,
+        };
+        let film_distance = camera.focus(focus_distance / scale);
+        camera.set_film_distance(film_distance);
+        camera
+    }
+
+    /// Positions the elements so that the rear element is `film_distance` away from the film
+    fn set_film_distance(&mut self, film_distance: f32) {
+        // the thickness of the rear element is ignored, its distance to the film is film_distance
+        let mut z = film_distance;
+        self.element_z = vec![z; self.elements.len()];
+        for i in (0..self.elements.len() - 1).rev() {
+            z += self.elements[i].thickness;
+            self.element_z[i] = z;
+        }
+    }
+
+    /// Finds the film distance that focuses a point `focus_distance` in front of the lens, using a paraxial ray
+    fn focus(&mut self, focus_distance: f32) -> f32 {
+        let film_distance = 1.;
+        self.set_film_distance(film_distance);
+
+        // a ray from the focused point on the axis, started just in front of the lens so far away
+        // focus distances do not lose the intersections to f32 precision
+        let front_z = self.element_z[0];
+        let height = self.elements[0].aperture * 0.05;
+        let start = self.elements[0].aperture.min(focus_distance);
+        let origin = Point::new(
+            height * (1. - start / focus_distance),
+            0.,
+            front_z + start,
+            0.,
+        );
+        let direction = (Point::new(height, 0., front_z, 0.) - origin).normalise();
+
+        match self.trace(origin, direction, false) {
+            // where the ray crosses the axis behind the lens is where the film should be
+            Some((origin, direction)) if direction.x() != 0. => {
+                let t = -origin.x() / direction.x();
+                film_distance - (origin.z() + direction.z() * t)
+            }
+            _ => film_distance,
+        }
+    }
+
+    /// Traces a ray in lens space through all the elements, returns None if the ray gets blocked
+    fn trace(
+        &self,
+        mut origin: Point,
+        mut direction: Vec4,
+        from_film: bool,
+    ) -> Option<(Point, Vec4)> {
+        let element_count = self.elements.len();
+        for step in 0..element_count {
+            let i = if from_film {
+                element_count - 1 - step
+            } else {
+                step
+            };
+            let element = &self.elements[i];
+            let vertex_z = self.element_z[i];
+
+            let (t, normal) = if element.radius == 0. {
+                // aperture stop, a plane perpendicular to the axis
+                if direction.z() == 0. {
+                    return None;
+                }
+                (
+                    (vertex_z - origin.z()) / direction.z(),
+                    Vec4::new(0., 0., 1., 0.),
+                )
+            } else {
+                Self::intersect_surface(element.radius, vertex_z, origin, direction)?
+            };
+            if t < 0. {
+                return None;
+            }
+
+            let hit = origin + direction * t;
+            if hit.x() * hit.x() + hit.y() * hit.y() > (element.aperture / 2.).powi(2) {
+                return None;
+            }
+            origin = hit;
+
+            if element.radius == 0. {
+                continue;
+            }
+
+            // the medium towards the scene of element i is the one behind element i - 1
+            let scene_side_index = if i == 0 {
+                1.
+            } else {
+                self.elements[i - 1].medium_index()
+            };
+            let relative_refractive_index = if from_film {
+                element.medium_index() / scene_side_index
+            } else {
+                scene_side_index / element.medium_index()
+            };
+
+            let normal = if normal.dot(direction) > 0. {
+                -normal
+            } else {
+                normal
+            };
+            let cos_theta = -direction.dot(normal);
+            let sin_theta_squared = 1. - cos_theta * cos_theta;
+            if relative_refractive_index.powi(2) * sin_theta_squared > 1. {
+                return None; // TIR
+            }
+            direction =
+                Dielectric::refract(normal, direction, relative_refractive_index).normalise();
+        }
+
+        Some((origin, direction))
+    }
+
+    /// Intersects a spherical surface given its vertex on the axis, returns t and the outward normal
+    fn intersect_surface(
+        radius: f32,
+        vertex_z: f32,
+        origin: Point,
+        direction: Vec4,
+    ) -> Option<(f32, Vec4)> {
+        let center = Point::new(0., 0., vertex_z - radius, 0.);
+        let oc = origin - center;
+        let half_b = oc.dot(direction);
+        let c = oc.dot(oc) - radius * radius;
+        let discriminant = half_b * half_b - c;
+        if discriminant < 0. {
+            return None;
+        }
+
+        // of the two intersections, the surface is the one closer to the vertex
+        let t = [-half_b - discriminant.sqrt(), -half_b + discriminant.sqrt()]
+            .into_iter()
+            .filter(|t| *t > 0.)
+            .min_by(|a, b| {
+                let distance = |t: f32| (origin.z() + direction.z() * t - vertex_z).abs();
+                distance(*a).total_cmp(&distance(*b))
+            })?;
+
+        let normal = (origin + direction * t - center).normalise();
+        Some((t, normal))
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
+        self.generate_weighted_ray(u, v)
+            .filter(|(_, weight)| *weight > 0.)
+            .map(|(ray, _)| ray)
+    }
+
+    /**
+    Rays blocked by the lens housing have a weight of 0. The others are weighted by cos⁴θ of their
+    angle to the axis at the film, light reaches the edges of the film at a grazing angle and from
+    further away (natural vignetting). The rear element (the exit pupil the rays are aimed at) has
+    the same area and distance from every film point, so that part of the exposure is constant and
+    left out, which keeps the center of the image at full brightness.
+
+    Blocked rays are kept as samples on purpose: the exposure of a film point is the average over
+    the whole rear element, so the fraction of it that the housing hides darkens the point as well
+    (optical vignetting). The two falloffs are separate effects and a real lens shows both.
+    */
+    fn generate_weighted_ray(&self, u: f32, v: f32) -> Option<(Ray, f32)> {
+        // the lens flips the image, so the film is flipped as well
+        let film_point = Point::new(
+            (0.5 - u) * self.film_width,
+            (0.5 - v) * self.film_height,
+            0.,
+            0.,
+        );
+
+        // aim at a random point on the rear element
+        let mut rng = rand::thread_rng();
+        let rear = self.elements[self.elements.len() - 1];
+        let r = (rear.aperture / 2.) * rng.gen::<f32>().sqrt();
+        let alpha = rng.gen::<f32>() * 2. * std::f32::consts::PI;
+        let rear_point = Point::new(
+            r * alpha.cos(),
+            r * alpha.sin(),
+            self.element_z[self.elements.len() - 1],
+            0.,
+        );
+        let direction = (rear_point - film_point).normalise();
+
+        // lens space to world space, the front vertex sits at the camera origin
+        let to_world = |v: Vec4| self.local_x * v.x() + self.local_y * v.y() - self.local_z * v.z();
+        let front = Point::new(0., 0., self.element_z[0], 0.);
+        let ray = |origin: Point, direction: Vec4| {
+            Ray::new(
+                self.origin + to_world(origin - front) * self.scale,
+                to_world(direction).normalise(),
+            )
+        };
+
+        match self.trace(film_point, direction, true) {
+            Some((origin, exit_direction)) => {
+                Some((ray(origin, exit_direction), direction.z().powi(4)))
+            }
+            None => Some((ray(film_point, direction), 0.)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_COUNT: u32 = 10_000;
+
+    /// Biconvex singlet, n = 1.5, 2 thick
+    const BICONVEX: &str = "
+    50     2   1.5   20
+    -50    0   1     20
+    ";
+
+    fn camera(prescription: &str, focus_distance: f32) -> RealisticCamera {
+        RealisticCamera::new(
+            LensElement::parse_prescription(prescription).unwrap(),
+            1.,
+            10.,
+            focus_distance,
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        )
+    }
+
+    #[test]
+    fn parses_prescription() {
+        let elements = LensElement::parse_prescription(DOUBLE_GAUSS_50MM).unwrap();
+        assert_eq!(elements.len(), 11);
+        assert_eq!(elements[0].radius, 29.475);
+        assert_eq!(elements[0].medium_index(), 1.67);
+
+        let stop = elements[5];
+        assert_eq!(stop.radius, 0.);
+        assert_eq!(stop.aperture, 17.1);
+        assert_eq!(stop.medium_index(), 1.);
+    }
+
+    #[test]
+    fn rejects_malformed_prescriptions() {
+        assert!(LensElement::parse_prescription("").is_err());
+        assert!(LensElement::parse_prescription("# radius thickness ior aperture").is_err());
+        assert_eq!(
+            LensElement::parse_prescription("50 2 1.5").err(),
+            Some(String::from(
+                "line 1: expected 4 columns, found 3"
+            ))
+        );
+        assert!(LensElement::parse_prescription("\n50 2 glass 20")
+            .err()
+            .is_some_and(|error| error.starts_with("line 2:")));
+    }
+
+    /// Film distance from the rear element against the thick lens equations, f = 50.34 here
+    #[test]
+    fn paraxial_focus_matches_thick_lens() {
+        // at infinity the film sits at the back focal length, f (1 - (n - 1) d / (n R1))
+        // at 200, 1/v = 1/f - 1/u with u and v measured from the principal planes
+        for (focus_distance, expected) in [(1e7, 49.664), (200., 66.518)] {
+            let camera = camera(BICONVEX, focus_distance);
+            let film_distance = camera.element_z[camera.elements.len() - 1];
+            assert!(
+                (film_distance - expected).abs() < 0.005 * expected,
+                "{} != {}",
+                film_distance,
+                expected
+            );
+        }
+    }
+
+    /// Blocked rays are kept as samples, so the housing darkens the corners (optical vignetting)
+    #[test]
+    fn housing_blocks_rays_towards_the_corners() {
+        let camera = RealisticCamera::new(
+            LensElement::parse_prescription(DOUBLE_GAUSS_50MM).unwrap(),
+            1.5,
+            43.3,
+            1000.,
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        );
+        let blocked_fraction = |u: f32, v: f32| {
+            let blocked = (0..SAMPLE_COUNT)
+                .filter(|_| camera.generate_weighted_ray(u, v).unwrap().1 <= 0.)
+                .count();
+            blocked as f32 / SAMPLE_COUNT as f32
+        };
+
+        let center = blocked_fraction(0.5, 0.5);
+        let corner = blocked_fraction(0., 0.);
+        assert!(center < corner, "{} >= {}", center, corner);
+        assert!(corner > 0.1, "{}", corner);
+    }
+}
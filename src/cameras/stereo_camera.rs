@@ -0,0 +1,220 @@
+use super::{
+    equirectangular_camera::EquirectangularCamera, perspective_camera::PerspectiveCamera, Eye,
+};
+use crate::ray_tracer::{interface::camera_base::Camera, utils::Ray};
+use crate::utils::vec4::{Point, Vec4};
+
+/// Where each eye is placed on the rendered image
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    /// left eye on the left half, right eye on the right half
+    SideBySide,
+    /// left eye on the top half, right eye on the bottom half
+    TopBottom,
+}
+
+// Renders both eyes into a single image, so a stereo pair is a single pass of Engine::render.
+// Each eye covers half of the image, so the eye cameras should be built with the aspect ratio of
+// that half (eg: half the image aspect ratio for side by side).
+pub struct StereoCamera {
+    layout: StereoLayout,
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+}
+
+impl StereoCamera {
+    /// Wraps any two cameras, eg: two fisheye cameras with parallel axes
+    pub fn new(layout: StereoLayout, left: Box<dyn Camera>, right: Box<dyn Camera>) -> Self {
+        Self {
+            layout,
+            left,
+            right,
+        }
+    }
+
+    /**
+    A pair of perspective cameras with parallel axes. Convergence is done by shifting the image
+    planes (off-axis) rather than rotating the cameras, which avoids vertical parallax.
+
+    - `aspect_ratio`: aspect ratio of a single eye
+    - `interocular_distance`: distance between the eyes in world units
+    - `convergence_distance`: distance from look_from at which the eyes converge (zero parallax)
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn perspective(
+        layout: StereoLayout,
+        aspect_ratio: f32,
+        focal_length: f32,
+        vfov: f32,
+        look_from: Point,
+        look_at: Point,
+        view_up: Vec4,
+        interocular_distance: f32,
+        convergence_distance: f32,
+    ) -> Self {
+        // local camera direction vectors
+        let local_z = (look_from - look_at).normalise();
+        let local_x = view_up.cross(local_z).normalise();
+
+        let eye = |sign: f32| {
+            let offset = local_x * (sign * interocular_distance / 2.);
+            let shift = offset * (-focal_length / convergence_distance);
+            PerspectiveCamera::new(
+                aspect_ratio,
+                focal_length,
+                vfov,
+                look_from + offset,
+                look_at + offset,
+                view_up,
+            )
+            .with_lens_shift(shift)
+        };
+
+        Self::new(layout, Box::new(eye(-1.)), Box::new(eye(1.)))
+    }
+
+    /// Omni-directional stereo pair of 360° equirectangular cameras
+    pub fn omni_directional(
+        layout: StereoLayout,
+        look_from: Point,
+        look_at: Point,
+        view_up: Vec4,
+        interocular_distance: f32,
+        convergence_distance: f32,
+    ) -> Self {
+        let eye = |eye: Eye| {
+            EquirectangularCamera::new(look_from, look_at, view_up).with_eye(
+                eye,
+                interocular_distance,
+                convergence_distance,
+            )
+        };
+
+        Self::new(
+            layout,
+            Box::new(eye(Eye::Left)),
+            Box::new(eye(Eye::Right)),
+        )
+    }
+}
+
+impl StereoCamera {
+    /// The camera of the eye that owns (u, v), and (u, v) remapped to the half of the image of that eye
+    fn eye(&self, u: f32, v: f32) -> (&dyn Camera, f32, f32) {
+        match self.layout {
+            StereoLayout::SideBySide => {
+                if u < 0.5 {
+                    (self.left.as_ref(), u * 2., v)
+                } else {
+                    (self.right.as_ref(), u * 2. - 1., v)
+                }
+            }
+            StereoLayout::TopBottom => {
+                if v >= 0.5 {
+                    (self.left.as_ref(), u, v * 2. - 1.)
+                } else {
+                    (self.right.as_ref(), u, v * 2.)
+                }
+            }
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray> {
+        let (camera, u, v) = self.eye(u, v);
+        camera.generate_ray(u, v)
+    }
+
+    fn generate_weighted_ray(&self, u: f32, v: f32) -> Option<(Ray, f32)> {
+        let (camera, u, v) = self.eye(u, v);
+        camera.generate_weighted_ray(u, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTEROCULAR_DISTANCE: f32 = 0.2;
+    const CONVERGENCE_DISTANCE: f32 = 5.;
+
+    /// Point where two rays cross (or pass closest), from the first ray's side
+    fn closest_point(a: &Ray, b: &Ray) -> Point {
+        // minimises |a.origin + a.direction * s - b.origin - b.direction * t|
+        let w = a.origin - b.origin;
+        let (d_a, d_b) = (a.direction.normalise(), b.direction.normalise());
+        let k = d_a.dot(d_b);
+        let s = (k * d_b.dot(w) - d_a.dot(w)) / (1. - k * k);
+        a.origin + d_a * s
+    }
+
+    #[test]
+    fn perspective_eyes_are_offset_and_converge() {
+        let camera = StereoCamera::perspective(
+            StereoLayout::SideBySide,
+            1.,
+            1.,
+            90.,
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+            INTEROCULAR_DISTANCE,
+            CONVERGENCE_DISTANCE,
+        );
+
+        // left eye on the left half, each eye is half the interocular distance to its side
+        let left = camera.generate_ray(0.25, 0.5).unwrap();
+        let right = camera.generate_ray(0.75, 0.5).unwrap();
+        let offset = Vec4::new(INTEROCULAR_DISTANCE / 2., 0., 0., 0.);
+        assert!((left.origin + offset).length() < 1e-5);
+        assert!((right.origin - offset).length() < 1e-5);
+
+        // the center rays meet at the convergence distance, straight ahead
+        let converged = closest_point(&left, &right);
+        assert!(
+            (converged - Point::new(0., 0., -CONVERGENCE_DISTANCE, 0.)).length() < 1e-3,
+            "{:?}",
+            converged.e
+        );
+    }
+
+    #[test]
+    fn omni_directional_eyes_circle_the_origin() {
+        let camera = StereoCamera::omni_directional(
+            StereoLayout::TopBottom,
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+            INTEROCULAR_DISTANCE,
+            CONVERGENCE_DISTANCE,
+        );
+
+        // left eye on the top half, looking forward (u = 0.5) and to the right (u = 0.75)
+        for (u, forward, left_side) in [
+            (
+                0.5,
+                Vec4::new(0., 0., -1., 0.),
+                Vec4::new(-1., 0., 0., 0.),
+            ),
+            (
+                0.75,
+                Vec4::new(1., 0., 0., 0.),
+                Vec4::new(0., 0., -1., 0.),
+            ),
+        ] {
+            let left = camera.generate_ray(u, 0.75).unwrap();
+            let right = camera.generate_ray(u, 0.25).unwrap();
+            let offset = left_side * (INTEROCULAR_DISTANCE / 2.);
+            assert!((left.origin - offset).length() < 1e-5);
+            assert!((right.origin + offset).length() < 1e-5);
+
+            let converged = closest_point(&left, &right);
+            assert!(
+                (converged - forward * CONVERGENCE_DISTANCE).length() < 1e-3,
+                "{:?}",
+                converged.e
+            );
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::ray_tracer::interface::{environment_base::Environment, light_base::LightSample};
+use crate::utils::{
+    distribution::Distribution2D,
+    image::Image,
+    vec4::{Color, Vec4},
+};
+use rand::prelude::*;
+use std::f32::consts::PI;
+
+/// Maps image co-ordinates to a unit direction, u => longitude from -z, v => angle from +y
+fn equirectangular_direction(u: f32, v: f32, rotation: f32) -> Vec4 {
+    let phi = (u - 0.5) * 2. * PI + rotation;
+    let theta = v * PI;
+    Vec4::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+        0.,
+    )
+}
+
+/**
+Image based lighting, light arriving from all directions as stored in an equirectangular (latitude
+/ longitude) HDR image around the scene. The center of the image is towards -z and the top row is
+straight up (+y). Directions are sampled proportional to the brightness of the pixels, so small
+bright features like the sun do not make the render noisy.
+*/
+pub struct EnvironmentMap {
+    image: Image,
+    distribution: Option<Distribution2D>, // over the image, u right and v down, None if it is empty
+    rotation: f32,                        // around +y, in radians
+    intensity: f32,
+}
+
+impl EnvironmentMap {
+    /// Pixels are used as they are, they should be linear (eg: HDR images)
+    pub fn new(image: Image) -> Self {
+        // rows near the poles cover a smaller solid angle
+        let mut function = Vec::with_capacity(image.pixels.len());
+        for y in 0..image.height {
+            let sin_theta = (PI * (y as f32 + 0.5) / image.height as f32).sin();
+            for x in 0..image.width {
+                function.push(image.pixel(x, y).luminance().max(0.) * sin_theta);
+            }
+        }
+
+        Self {
+            distribution: Distribution2D::new(&function, image.width, image.height),
+            image,
+            rotation: 0.,
+            intensity: 1.,
+        }
+    }
+
+    /// Renders the light arriving from every direction (eg: an analytic sky) into a `width` x `height` image
+    pub fn from_fn(width: usize, height: usize, radiance: impl Fn(Vec4) -> Color) -> Self {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(radiance(equirectangular_direction(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                    0.,
+                )));
+            }
+        }
+
+        Self::new(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Loads a Radiance HDR file, or any other image format [`Image::load`] supports
+    pub fn load(path: &str) -> Result<Self, String> {
+        Ok(Self::new(Image::load(path)?))
+    }
+
+    /// Turns the environment around the up axis (+y), `rotation` is in degrees
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation.to_radians();
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Maps a unit direction to image co-ordinates in [0, 1)²
+    fn to_image(&self, direction: Vec4) -> (f32, f32) {
+        let phi = direction.x().atan2(-direction.z()) - self.rotation;
+        let u = (phi / (2. * PI) + 0.5).rem_euclid(1.);
+        let v = direction.y().clamp(-1., 1.).acos() / PI;
+        (u.min(1. - f32::EPSILON), v.min(1. - f32::EPSILON))
+    }
+
+    fn direction(&self, u: f32, v: f32) -> Vec4 {
+        equirectangular_direction(u, v, self.rotation)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vec4) -> Color {
+        if self.image.pixels.is_empty() {
+            return Color::new(0., 0., 0., 1.);
+        }
+
+        let (u, v) = self.to_image(direction.normalise());
+        let x = ((u * self.image.width as f32) as usize).min(self.image.width - 1);
+        let y = ((v * self.image.height as f32) as usize).min(self.image.height - 1);
+
+        let mut radiance = self.image.pixel(x, y) * self.intensity;
+        radiance[3] = 1.;
+        radiance
+    }
+
+    fn sample(&self) -> Option<LightSample> {
+        let mut rng = rand::thread_rng();
+        let ((u, v), _) = self.distribution.as_ref()?.sample(rng.gen(), rng.gen());
+
+        // looked up from the direction, so it matches `pdf` and `radiance` on the edges of pixels
+        let direction = self.direction(u, v);
+        let pdf = self.pdf(direction);
+        if pdf <= 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance: f32::INFINITY,
+            light: self.radiance(direction) / pdf,
+            pdf,
+        })
+    }
+
+    fn pdf(&self, direction: Vec4) -> f32 {
+        let direction = direction.normalise();
+        let sin_theta = (1. - direction.y() * direction.y()).max(0.).sqrt();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        let Some(distribution) = &self.distribution else {
+            return 0.;
+        };
+        let (u, v) = self.to_image(direction);
+        distribution.pdf(u, v) / (2. * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_COUNT: u32 = 100_000;
+
+    /// Dark environment with a small, very bright patch
+    fn sun_map() -> EnvironmentMap {
+        let (width, height) = (64, 32);
+        let mut pixels = vec![Color::new(0.1, 0.2, 0.3, 1.); width * height];
+        for y in 8..10 {
+            for x in 40..42 {
+                pixels[y * width + x] = Color::new(500., 450., 400., 1.);
+            }
+        }
+        EnvironmentMap::new(Image {
+            width,
+            height,
+            pixels,
+        })
+        .with_rotation(30.)
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let map = sun_map();
+        for sample in (0..1000).filter_map(|_| map.sample()) {
+            let pdf = map.pdf(sample.direction);
+            assert!(
+                (sample.pdf - pdf).abs() <= 1e-3 * pdf,
+                "{} != {}",
+                sample.pdf,
+                pdf
+            );
+        }
+    }
+
+    /// Light arriving on an upwards facing surface, importance sampled vs integrated over the image
+    #[test]
+    fn importance_sampling_is_unbiased() {
+        let map = sun_map();
+
+        // samples right on the poles have no density and are dropped, they add nothing
+        let mut importance = 0.;
+        for sample in (0..SAMPLE_COUNT).filter_map(|_| map.sample()) {
+            importance += sample.light.x() * sample.direction.y().max(0.);
+        }
+        let importance = importance / SAMPLE_COUNT as f32;
+
+        // midpoint rule, a few steps per pixel
+        let (columns, rows) = (map.image.width * 4, map.image.height * 4);
+        let mut integral = 0.;
+        for row in 0..rows {
+            let theta = PI * (row as f32 + 0.5) / rows as f32;
+            for column in 0..columns {
+                let u = (column as f32 + 0.5) / columns as f32;
+                let direction = map.direction(u, theta / PI);
+                integral += map.radiance(direction).x() * direction.y().max(0.) * theta.sin();
+            }
+        }
+        let integral = integral * (PI / rows as f32) * (2. * PI / columns as f32);
+
+        assert!(
+            (importance - integral).abs() < 0.01 * integral,
+            "{} != {}",
+            importance,
+            integral
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use crate::ray_tracer::{interface::environment_base::Environment, utils::map_to_range};
+use crate::utils::vec4::{Color, Vec4};
+
+const WHITE: Color = Color {
+    e: [1., 1., 1., 1.],
+};
+const BLUE: Color = Color {
+    e: [130. / 255., 170. / 255., 227. / 255., 1.],
+};
+
+/// Blends from `bottom` straight down to `top` straight up
+pub struct GradientEnvironment {
+    bottom: Color,
+    top: Color,
+}
+
+impl GradientEnvironment {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+}
+
+/// White to light blue, a simple sky
+impl Default for GradientEnvironment {
+    fn default() -> Self {
+        Self::new(WHITE, BLUE)
+    }
+}
+
+impl Environment for GradientEnvironment {
+    fn radiance(&self, direction: Vec4) -> Color {
+        let y = direction.normalise().y(); // -1 <= y <= 1
+        let t = map_to_range(y, -1., 1., 0., 1.);
+
+        let mut radiance = self.bottom * (1. - t) + self.top * t;
+        radiance[3] = 1.;
+        radiance
+    }
+}
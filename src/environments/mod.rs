@@ -0,0 +1,5 @@
+pub mod environment_map;
+pub mod gradient_environment;
+pub mod sky;
+pub mod solid_environment;
+pub mod texture_environment;
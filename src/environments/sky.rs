@@ -0,0 +1,231 @@
+use super::environment_map::EnvironmentMap;
+use crate::{
+    lights::directional_light::DirectionalLight,
+    ray_tracer::{
+        interface::{environment_base::Environment, light_base::LightSample},
+        spectrum::{blackbody, photometric_xyz_to_rgb, spectrum_to_rgb},
+    },
+    utils::vec4::{Color, Vec4},
+};
+use std::{cell::OnceCell, f32::consts::PI};
+
+const SUN_TEMPERATURE: f32 = 5778.; // K, the sun's surface seen from outside of the atmosphere
+const SUN_ANGULAR_DIAMETER: f32 = 0.53; // degrees
+const SUN_SAMPLES: usize = 32;
+
+// resolution of the map directions are sampled from, the radiance itself is always exact
+const IMPORTANCE_WIDTH: usize = 256;
+const IMPORTANCE_HEIGHT: usize = 128;
+
+/// Perez et al. sky luminance distribution, relative to its value at the zenith
+#[derive(Clone, Copy)]
+struct Perez([f32; 5]);
+
+impl Perez {
+    /// `theta`: angle from the zenith, `gamma`: angle from the sun
+    fn f(&self, theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = self.0;
+        (1. + a * (b / theta.cos()).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+}
+
+/**
+Clear daylight sky (Preetham et al. 1999, "A Practical Analytic Model for Daylight") lit by the sun,
+with the sun itself as a directional light (see [`Sky::sun`]). Radiance is in the same units as
+[`blackbody`] (daylight of 1 W / (sr m² nm) at 560 nm is white 1), so the sky and the sun keep
+their physical ratio, a white surface in full sun is about 0.5.
+
+- `elevation`: angle of the sun above the horizon in degrees
+- `azimuth`: angle of the sun around the up axis (+y) in degrees, 0 is towards -z, 90 towards +x
+- `turbidity`: haziness of the atmosphere, 2 is a very clear sky, 10 a hazy one
+*/
+pub struct Sky {
+    to_sun: Vec4,
+    sun_zenith: f32, // angle between the sun and the zenith, in radians
+    turbidity: f32,
+    zenith: [f32; 3], // Y (in cd / m²), x and y at the zenith
+    distributions: [Perez; 3],
+    intensity: f32,
+    importance: OnceCell<EnvironmentMap>, // the sky baked at a low resolution, built when first sampled
+}
+
+impl Sky {
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> Self {
+        let elevation = elevation.clamp(0., 90.).to_radians();
+        let azimuth = azimuth.to_radians();
+        let t = turbidity.clamp(1.7, 10.); // range the model was fitted to
+        let theta = PI / 2. - elevation;
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) * 1000.;
+        let chromaticity = |coefficients: [[f32; 4]; 3]| {
+            let polynomial =
+                |c: [f32; 4]| c[0] * theta.powi(3) + c[1] * theta.powi(2) + c[2] * theta + c[3];
+            t * t * polynomial(coefficients[0]) +
+                t * polynomial(coefficients[1]) +
+                polynomial(coefficients[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        Self {
+            to_sun: Vec4::new(
+                elevation.cos() * azimuth.sin(),
+                elevation.sin(),
+                -elevation.cos() * azimuth.cos(),
+                0.,
+            ),
+            sun_zenith: theta,
+            turbidity: t,
+            zenith: [zenith_luminance.max(0.), zenith_x, zenith_y],
+            distributions: [
+                Perez([
+                    0.1787 * t - 1.4630,
+                    -0.3554 * t + 0.4275,
+                    -0.0227 * t + 5.3251,
+                    0.1206 * t - 2.5771,
+                    -0.0670 * t + 0.3703,
+                ]),
+                Perez([
+                    -0.0193 * t - 0.2592,
+                    -0.0665 * t + 0.0008,
+                    -0.0004 * t + 0.2125,
+                    -0.0641 * t - 0.8989,
+                    -0.0033 * t + 0.0452,
+                ]),
+                Perez([
+                    -0.0167 * t - 0.2608,
+                    -0.0950 * t + 0.0092,
+                    -0.0079 * t + 0.2102,
+                    -0.0441 * t - 1.6537,
+                    -0.0109 * t + 0.0529,
+                ]),
+            ],
+            intensity: 1.,
+            importance: OnceCell::new(),
+        }
+    }
+
+    /// Scales the sky and the sun together, like the exposure of a camera
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /**
+    The sun as seen through the atmosphere, attenuated by Rayleigh scattering off air molecules and
+    by aerosols (haze), which makes it redder towards the horizon. Absorption by ozone and water
+    vapour is left out.
+    */
+    pub fn sun(&self) -> DirectionalLight {
+        // relative optical mass, how much more air the light goes through than from the zenith
+        let zenith_degrees = self.sun_zenith.to_degrees();
+        let air_mass = 1. / (self.sun_zenith.cos() + 0.15 * (93.885 - zenith_degrees).powf(-1.253));
+        let aerosol_density = 0.04608 * self.turbidity - 0.04586;
+
+        let radiance = spectrum_to_rgb(
+            |wavelength| {
+                let micrometers = wavelength / 1000.;
+                let rayleigh = (-0.008735 * micrometers.powf(-4.08) * air_mass).exp();
+                let aerosol = (-aerosol_density * micrometers.powf(-1.3) * air_mass).exp();
+                blackbody(wavelength, SUN_TEMPERATURE) * rayleigh * aerosol
+            },
+            SUN_SAMPLES,
+        );
+
+        // light arriving on a surface facing the sun is its radiance times the solid angle of its disk
+        let solid_angle = 2. * PI * (1. - (SUN_ANGULAR_DIAMETER / 2.).to_radians().cos());
+        DirectionalLight::new(
+            -self.to_sun,
+            radiance,
+            solid_angle * self.intensity,
+        )
+        .with_angular_diameter(SUN_ANGULAR_DIAMETER)
+    }
+
+    fn importance(&self) -> &EnvironmentMap {
+        self.importance.get_or_init(|| {
+            EnvironmentMap::from_fn(IMPORTANCE_WIDTH, IMPORTANCE_HEIGHT, |direction| {
+                self.radiance(direction)
+            })
+        })
+    }
+}
+
+impl Environment for Sky {
+    /// Without the sun, it is a light of its own. The ground below the horizon is black.
+    fn radiance(&self, direction: Vec4) -> Color {
+        let direction = direction.normalise();
+        if direction.y() <= 0. {
+            return Color::new(0., 0., 0., 1.);
+        }
+
+        // the model breaks down right at the horizon
+        let theta = direction.y().max(0.01).acos();
+        let gamma = direction.dot(self.to_sun).clamp(-1., 1.).acos();
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            self.zenith[i] * self.distributions[i].f(theta, gamma) /
+                self.distributions[i].f(0., self.sun_zenith)
+        });
+        if y <= 0. {
+            return Color::new(0., 0., 0., 1.);
+        }
+
+        photometric_xyz_to_rgb([x / y * luminance, luminance, (1. - x - y) / y * luminance]) *
+            self.intensity
+    }
+
+    /// Directions are picked from the baked sky, weighted by the exact radiance
+    fn sample(&self) -> Option<LightSample> {
+        let sample = self.importance().sample()?;
+        Some(LightSample {
+            light: self.radiance(sample.direction) / sample.pdf,
+            ..sample
+        })
+    }
+
+    fn pdf(&self, direction: Vec4) -> f32 {
+        self.importance().pdf(direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray_tracer::interface::light_base::Light, utils::vec4::Point};
+
+    fn sun_light(elevation: f32) -> Color {
+        let sample = Sky::new(elevation, 0., 3.)
+            .sun()
+            .sample(&Point::new(0., 0., 0., 1.))
+            .unwrap();
+        sample.light
+    }
+
+    #[test]
+    fn setting_sun_is_dimmer_and_redder() {
+        let noon = sun_light(70.);
+        let evening = sun_light(5.);
+        assert!(evening.y() < noon.y());
+        assert!(evening.x() / evening.z() > noon.x() / noon.z());
+    }
+
+    #[test]
+    fn sky_is_blue_and_ground_is_black() {
+        let sky = Sky::new(45., 0., 2.5);
+        let zenith = sky.radiance(Vec4::new(0., 1., 0., 0.));
+        assert!(zenith.z() > zenith.x() && zenith.y() > 0.);
+        assert_eq!(
+            sky.radiance(Vec4::new(0., -1., 0.3, 0.)).e,
+            [0., 0., 0., 1.]
+        );
+    }
+}
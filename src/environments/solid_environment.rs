@@ -0,0 +1,21 @@
+use crate::ray_tracer::interface::environment_base::Environment;
+use crate::utils::vec4::{Color, Vec4};
+
+/// The same light arriving from every direction (eg: an overcast day, a studio backdrop)
+pub struct SolidEnvironment {
+    color: Color,
+}
+
+impl SolidEnvironment {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for SolidEnvironment {
+    fn radiance(&self, _direction: Vec4) -> Color {
+        let mut radiance = self.color;
+        radiance[3] = 1.;
+        radiance
+    }
+}
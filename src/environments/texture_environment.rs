@@ -0,0 +1,33 @@
+use crate::ray_tracer::interface::{environment_base::Environment, texture_base::Texture};
+use crate::utils::vec4::{Color, Vec4};
+use std::{f32::consts::PI, rc::Rc};
+
+/**
+Any texture wrapped around the scene, u follows the longitude (0.5 is towards -z) and v the
+latitude (1 is straight up). Image textures should be equirectangular, an [`EnvironmentMap`] lights
+the scene with less noise for HDR images.
+
+[`EnvironmentMap`]: super::environment_map::EnvironmentMap
+*/
+pub struct TextureEnvironment {
+    texture: Rc<dyn Texture>,
+}
+
+impl TextureEnvironment {
+    pub fn new(texture: Rc<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl Environment for TextureEnvironment {
+    fn radiance(&self, direction: Vec4) -> Color {
+        let direction = direction.normalise();
+        let u = (direction.x().atan2(-direction.z()) / (2. * PI) + 0.5).rem_euclid(1.);
+        let v = 1. - direction.y().clamp(-1., 1.).acos() / PI;
+
+        // solid textures get the direction, on the unit sphere
+        let mut radiance = self.texture.value(u, v, &direction);
+        radiance[3] = 1.;
+        radiance
+    }
+}
@@ -0,0 +1,9 @@
+pub mod cameras;
+pub mod environments;
+pub mod lights;
+pub mod materials;
+pub mod objects;
+pub mod ray_tracer;
+pub mod scene;
+pub mod textures;
+pub mod utils;
@@ -0,0 +1,66 @@
+use crate::ray_tracer::interface::light_base::{Light, LightSample};
+use crate::utils::{
+    frame::Frame,
+    vec4::{Color, Point, Vec4},
+};
+use rand::prelude::*;
+use std::f32::consts::PI;
+
+/**
+Light from a source so far away that it arrives from the same direction everywhere (eg: the sun).
+A non zero angular diameter gives soft shadows, the sun is about 0.53 degrees across.
+*/
+pub struct DirectionalLight {
+    to_light: Vec4, // unit vector towards the light
+    light: Color,   // color times intensity, light arriving on a surface facing the light
+    cos_angular_radius: f32,
+}
+
+impl DirectionalLight {
+    /// `direction` is the direction the light travels in
+    pub fn new(direction: Vec4, color: Color, intensity: f32) -> Self {
+        Self {
+            to_light: -direction.normalise(),
+            light: color * intensity,
+            cos_angular_radius: 1.,
+        }
+    }
+
+    /// Size of the light in the sky, in degrees
+    pub fn with_angular_diameter(mut self, angular_diameter: f32) -> Self {
+        self.cos_angular_radius = (angular_diameter.clamp(0., 180.) / 2.).to_radians().cos();
+        self
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Point) -> Option<LightSample> {
+        let direction = if self.cos_angular_radius < 1. {
+            // uniformly over the disk of the light in the sky
+            let mut rng = rand::thread_rng();
+            let cos_theta = 1. - rng.gen::<f32>() * (1. - self.cos_angular_radius);
+            let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+            let phi = 2. * PI * rng.gen::<f32>();
+            Frame::from_normal(self.to_light).to_world(Vec4::new(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+                0.,
+            ))
+        } else {
+            self.to_light
+        };
+
+        Some(LightSample {
+            direction,
+            distance: f32::INFINITY,
+            light: self.light,
+            pdf: f32::INFINITY,
+        })
+    }
+
+    /// The light reaches everywhere, so this is per unit of area facing it
+    fn power(&self) -> f32 {
+        self.light.luminance().max(0.)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod directional_light;
+pub mod point_light;
+pub mod sphere_light;
+pub mod spot_light;
\ No newline at end of file
@@ -0,0 +1,64 @@
+use crate::ray_tracer::interface::light_base::{Light, LightSample};
+use crate::utils::vec4::{Color, Point};
+use std::f32::consts::PI;
+
+/// How the light of a point light gets weaker with distance
+#[derive(Clone, Copy)]
+pub enum Falloff {
+    /// 1 / d², physically correct
+    InverseSquare,
+    /// 1 / d, softer than physical, lights far away things more evenly
+    Linear,
+    /// same brightness at any distance
+    Constant,
+}
+
+/// Light given off equally in all directions from a single point (eg: a bare bulb)
+pub struct PointLight {
+    position: Point,
+    light: Color, // color times intensity
+    falloff: Falloff,
+}
+
+impl PointLight {
+    pub fn new(position: Point, color: Color, intensity: f32) -> Self {
+        Self {
+            position,
+            light: color * intensity,
+            falloff: Falloff::InverseSquare,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Point) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.length();
+        if distance <= 0. {
+            return None;
+        }
+
+        let attenuation = match self.falloff {
+            Falloff::InverseSquare => 1. / (distance * distance),
+            Falloff::Linear => 1. / distance,
+            Falloff::Constant => 1.,
+        };
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            light: self.light * attenuation,
+            pdf: f32::INFINITY,
+        })
+    }
+
+    /// Given off equally over the whole sphere of directions
+    fn power(&self) -> f32 {
+        4. * PI * self.light.luminance().max(0.)
+    }
+}
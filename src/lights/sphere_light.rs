@@ -0,0 +1,186 @@
+use crate::objects::sphere::Sphere;
+use crate::ray_tracer::{
+    interface::{
+        light_base::{Light, LightSample},
+        object_base::Object,
+    },
+    utils::Ray,
+};
+use crate::utils::{
+    frame::Frame,
+    vec4::{Point, Vec4},
+};
+use rand::prelude::*;
+use std::f32::consts::PI;
+
+// shadow rays stop just short of the sampled point, so they do not hit the light itself
+const SHADOW_RAY_FRACTION: f32 = 0.999;
+
+/**
+Light given off by a sphere with an emissive material (eg: a light bulb), found by shadow rays as
+well as by the rays materials scatter. Directions are sampled uniformly in the cone the sphere
+covers from the point being lit. The light shares the sphere's object id, so the engine can tell
+when a ray hits it, see [`Scene::add_sphere_light`](crate::scene::Scene::add_sphere_light).
+*/
+pub struct SphereLight {
+    sphere: Sphere,
+    power: f32,
+}
+
+impl SphereLight {
+    pub fn new(sphere: &Sphere) -> Self {
+        // emission seen straight on at the top, assumed to be the same all over the sphere
+        let radius = sphere.radius().abs();
+        let ray = Ray::new(
+            sphere.center() + Vec4::new(0., 2. * radius, 0., 0.),
+            Vec4::new(0., -1., 0., 0.),
+        );
+        let radiance = sphere
+            .is_ray_hit(&ray, 0., f32::INFINITY)
+            .map_or(0., |hit_record| {
+                hit_record.material.emitted(&ray, &hit_record).luminance()
+            });
+
+        Self {
+            sphere: sphere.clone(),
+            // π times the radiance leaves the surface per unit area
+            power: PI * radiance.max(0.) * 4. * PI * radius * radius,
+        }
+    }
+
+    /**
+    Cone of directions the sphere covers from `point`.
+
+    Returns:
+
+    The unit direction towards the center and 1 - cos of the half angle of the cone, or None if the
+    point is inside of the sphere
+    */
+    fn cone(&self, point: &Point) -> Option<(Vec4, f32)> {
+        let to_center = self.sphere.center() - *point;
+        let distance_squared = to_center.dot(to_center);
+        let radius_squared = self.sphere.radius() * self.sphere.radius();
+        if distance_squared <= radius_squared {
+            return None;
+        }
+
+        // 1 - cos written with sin² so far away lights do not lose their precision
+        let sin_squared = radius_squared / distance_squared;
+        let cos_max = (1. - sin_squared).max(0.).sqrt();
+        Some((
+            to_center.normalise(),
+            sin_squared / (1. + cos_max),
+        ))
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, point: &Point) -> Option<LightSample> {
+        let (to_center, one_minus_cos_max) = self.cone(point)?;
+
+        let mut rng = rand::thread_rng();
+        let cos_theta = 1. - rng.gen::<f32>() * one_minus_cos_max;
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * rng.gen::<f32>();
+        let direction = Frame::from_normal(to_center)
+            .to_world(Vec4::new(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+                0.,
+            ))
+            .normalise();
+
+        // grazing directions can miss the sphere by a rounding error
+        let ray = Ray::new(*point, direction);
+        let hit_record = self.sphere.is_ray_hit(&ray, 0., f32::INFINITY)?;
+        let pdf = 1. / (2. * PI * one_minus_cos_max);
+
+        Some(LightSample {
+            direction,
+            distance: hit_record.t * SHADOW_RAY_FRACTION,
+            light: hit_record.material.emitted(&ray, &hit_record) / pdf,
+            pdf,
+        })
+    }
+
+    fn power(&self) -> f32 {
+        self.power
+    }
+
+    fn pdf(&self, point: &Point, direction: Vec4) -> f32 {
+        let Some((_, one_minus_cos_max)) = self.cone(point) else {
+            return 0.;
+        };
+        let ray = Ray::new(*point, direction);
+        if self.sphere.is_ray_hit(&ray, 0., f32::INFINITY).is_none() {
+            return 0.;
+        }
+        1. / (2. * PI * one_minus_cos_max)
+    }
+
+    fn object_id(&self) -> Option<usize> {
+        Some(self.sphere.object_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{materials::emissive::Emissive, utils::vec4::Color};
+    use std::rc::Rc;
+
+    const SAMPLE_COUNT: u32 = 10_000;
+
+    /// Sphere of radius 1 giving off a radiance of 2, 4 units above the origin
+    fn light() -> SphereLight {
+        SphereLight::new(&Sphere::new(
+            1.,
+            Point::new(0., 4., 0., 0.),
+            Rc::new(Emissive::new(Color::new(1., 1., 1., 1.), 2.)),
+        ))
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let light = light();
+        let point = Point::new(0.5, 0., 0.2, 0.);
+        for sample in (0..1000).filter_map(|_| light.sample(&point)) {
+            let pdf = light.pdf(&point, sample.direction);
+            assert!(
+                (sample.pdf - pdf).abs() <= 1e-3 * pdf,
+                "{} != {}",
+                sample.pdf,
+                pdf
+            );
+        }
+        assert_eq!(light.pdf(&point, Vec4::new(0., -1., 0., 0.)), 0.);
+    }
+
+    /// Light arriving on an upwards facing surface right under the sphere, π L sin²θ
+    #[test]
+    fn irradiance_matches_closed_form() {
+        let light = light();
+        let point = Point::new(0., 0., 0., 0.);
+        let mut sum = 0.;
+        for _ in 0..SAMPLE_COUNT {
+            if let Some(sample) = light.sample(&point) {
+                sum += sample.light.y() * sample.direction.y();
+            }
+        }
+        let irradiance = sum / SAMPLE_COUNT as f32;
+        let expected = PI * 2. / 16.;
+        assert!(
+            (irradiance - expected).abs() < 0.01 * expected,
+            "{} != {}",
+            irradiance,
+            expected
+        );
+    }
+
+    #[test]
+    fn points_inside_are_not_lit() {
+        let light = light();
+        assert!(light.sample(&Point::new(0., 4.5, 0., 0.)).is_none());
+    }
+}
@@ -0,0 +1,66 @@
+use super::point_light::{Falloff, PointLight};
+use crate::ray_tracer::interface::light_base::{Light, LightSample};
+use crate::utils::vec4::{Color, Point, Vec4};
+
+/**
+Point light shining in a cone (eg: a stage light or a torch). The light is at full strength inside
+the inner cone and fades out smoothly towards the outer cone.
+
+- `inner_angle`, `outer_angle`: angles between the axis of the cone and its edges, in degrees
+*/
+pub struct SpotLight {
+    light: PointLight,
+    axis: Vec4, // unit vector the light shines along
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point,
+        look_at: Point,
+        color: Color,
+        intensity: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        let outer_angle = outer_angle.clamp(0., 180.);
+        let inner_angle = inner_angle.clamp(0., outer_angle);
+
+        Self {
+            light: PointLight::new(position, color, intensity),
+            axis: (look_at - position).normalise(),
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.light = self.light.with_falloff(falloff);
+        self
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Point) -> Option<LightSample> {
+        let mut sample = self.light.sample(point)?;
+
+        let cos_theta = -sample.direction.dot(self.axis);
+        if cos_theta <= self.cos_outer {
+            return None;
+        }
+        if cos_theta < self.cos_inner {
+            // smoothstep between the cones
+            let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+            sample.light *= t * t * (3. - 2. * t);
+        }
+
+        Some(sample)
+    }
+
+    /// Only the part of the sphere inside of the cone, the fade counted as half
+    fn power(&self) -> f32 {
+        let cos_average = (self.cos_inner + self.cos_outer) / 2.;
+        self.light.power() * (1. - cos_average) / 2.
+    }
+}
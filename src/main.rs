@@ -1,20 +1,21 @@
-use crate::cameras::perspective_camera::PerspectiveCamera;
-use crate::materials::dielectric::Dielectric;
-use crate::materials::lambertian::Lambertian;
-use crate::materials::metal::Metal;
-use crate::objects::sphere::Sphere;
-use crate::ray_tracer::{engine::Engine, interface::camera_base::Camera};
-use crate::scene::Scene;
-use crate::utils::vec4::{Color, Point, Vec4};
+use kiroshi::cameras::cubemap_camera::CubemapCamera;
+use kiroshi::cameras::equirectangular_camera::EquirectangularCamera;
+use kiroshi::cameras::fisheye_camera::{FisheyeCamera, FisheyeProjection};
+use kiroshi::cameras::orthographic_camera::OrthographicCamera;
+use kiroshi::cameras::perspective_camera::PerspectiveCamera;
+use kiroshi::cameras::realistic_camera::{LensElement, RealisticCamera, DOUBLE_GAUSS_50MM};
+use kiroshi::cameras::stereo_camera::{StereoCamera, StereoLayout};
+use kiroshi::materials::dielectric::Dielectric;
+use kiroshi::materials::lambertian::Lambertian;
+use kiroshi::materials::metal::Metal;
+use kiroshi::objects::sphere::Sphere;
+use kiroshi::ray_tracer::{
+    engine::Engine, interface::camera_base::Camera, light_sampler::LightSelection,
+};
+use kiroshi::scene::Scene;
+use kiroshi::utils::vec4::{Color, Point, Vec4};
 use std::rc::Rc;
 
-mod cameras;
-mod materials;
-mod objects;
-mod ray_tracer;
-mod scene;
-mod utils;
-
 // * This is the rendered image (the canvas) dimensions
 // * This as of now matches the virtual viewport AR for square pixels
 //TODO: Having issues with 16/9 (non-integral values for AR)
@@ -23,6 +24,33 @@ const IMAGE_HEIGHT: u32 = 256;
 const IMAGE_WIDTH: u32 = (ASPECT_RATIO * (IMAGE_HEIGHT as f32)) as u32;
 
 const FOCAL_LENGTH: f32 = 1.0;
+const ORTHOGRAPHIC_VIEW_HEIGHT: f32 = 3.0;
+const INTEROCULAR_DISTANCE: f32 = 0.064;
+const CONVERGENCE_DISTANCE: f32 = 3.0;
+const FILM_DIAGONAL: f32 = 43.3; // 35mm film, in mm
+const LENS_SCALE: f32 = 0.001; // lens prescriptions are in mm
+
+const CAMERAS: [&str; 8] = [
+    "perspective",
+    "orthographic",
+    "equirectangular",
+    "fisheye",
+    "cubemap",
+    "stereo",
+    "ods",
+    "realistic",
+];
+const LIGHT_SELECTIONS: [&str; 4] = ["all", "uniform", "power", "contribution"];
+
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{}", error);
+    eprintln!(
+        "usage: kiroshi [{}] [--spectral] [--light-selection=<{}>] [--light-stats]",
+        CAMERAS.join("|"),
+        LIGHT_SELECTIONS.join("|")
+    );
+    std::process::exit(1);
+}
 
 fn main() {
     let mat_ground = Rc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0, 1.0)));
@@ -61,15 +89,78 @@ fn main() {
         mat_ground,
     )));
 
-    let camera: Box<dyn Camera> = Box::new(PerspectiveCamera::new(
-        ASPECT_RATIO,
-        FOCAL_LENGTH,
-        90.,
-        Point::new(-2., 2., 1., 0.),
-        Point::new(0., 0., -1., 0.),
-        Vec4::new(0., 1., 0., 0.),
-    ));
-    let engine = Engine::new(
+    let look_from = Point::new(-2., 2., 1., 0.);
+    let look_at = Point::new(0., 0., -1., 0.);
+    let view_up = Vec4::new(0., 1., 0., 0.);
+
+    // * Camera is picked using the first CLI argument that is not a flag, defaults to perspective
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let camera_name = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map_or("perspective", String::as_str);
+    let camera: Box<dyn Camera> = match camera_name {
+        "orthographic" => Box::new(OrthographicCamera::new(
+            ORTHOGRAPHIC_VIEW_HEIGHT * ASPECT_RATIO,
+            ORTHOGRAPHIC_VIEW_HEIGHT,
+            look_from,
+            look_at,
+            view_up,
+        )),
+        "equirectangular" => Box::new(EquirectangularCamera::new(
+            look_from, look_at, view_up,
+        )),
+        "fisheye" => Box::new(FisheyeCamera::new(
+            FisheyeProjection::Equisolid,
+            ASPECT_RATIO,
+            180.,
+            look_from,
+            look_at,
+            view_up,
+        )),
+        // * Faces get stretched unless the aspect ratio is 3:2
+        "cubemap" => Box::new(CubemapCamera::new(look_from, look_at, view_up)),
+        "stereo" => Box::new(StereoCamera::perspective(
+            StereoLayout::SideBySide,
+            ASPECT_RATIO / 2.,
+            FOCAL_LENGTH,
+            90.,
+            look_from,
+            look_at,
+            view_up,
+            INTEROCULAR_DISTANCE,
+            CONVERGENCE_DISTANCE,
+        )),
+        // * Each eye gets stretched unless the aspect ratio is 1:1
+        "ods" => Box::new(StereoCamera::omni_directional(
+            StereoLayout::TopBottom,
+            look_from,
+            look_at,
+            view_up,
+            INTEROCULAR_DISTANCE,
+            CONVERGENCE_DISTANCE,
+        )),
+        "realistic" => Box::new(RealisticCamera::new(
+            LensElement::parse_prescription(DOUBLE_GAUSS_50MM).unwrap(),
+            ASPECT_RATIO,
+            FILM_DIAGONAL,
+            (look_at - look_from).length(),
+            LENS_SCALE,
+            look_from,
+            look_at,
+            view_up,
+        )),
+        "perspective" => Box::new(PerspectiveCamera::new(
+            ASPECT_RATIO,
+            FOCAL_LENGTH,
+            90.,
+            look_from,
+            look_at,
+            view_up,
+        )),
+        _ => exit_with_usage(&format!("unknown camera '{}'", camera_name)),
+    };
+    let mut engine = Engine::new(
         camera,
         scene,
         IMAGE_HEIGHT,
@@ -77,8 +168,36 @@ fn main() {
         true,
         100,
     );
+    // * Spectral rendering is turned on with the --spectral flag
+    if args.iter().any(|arg| arg == "--spectral") {
+        engine = engine.with_spectral_rendering();
+    }
+    // * Lights are picked with the --light-selection=<...> flag, every light at every hit by default
+    if let Some(selection) = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--light-selection="))
+    {
+        let selection = match selection {
+            "all" => LightSelection::All,
+            "uniform" => LightSelection::Uniform,
+            "power" => LightSelection::Power,
+            "contribution" => LightSelection::Contribution,
+            _ => exit_with_usage(&format!(
+                "unknown light selection '{}'",
+                selection
+            )),
+        };
+        engine = engine.with_light_selection(selection);
+    }
     let output: Vec<Vec<Color>> = engine.render();
 
+    // * Light samples per light are reported with the --light-stats flag (on stderr, the image is on stdout)
+    if args.iter().any(|arg| arg == "--light-stats") {
+        for (light, count) in engine.light_sample_counts().iter().enumerate() {
+            eprintln!("light {}: {} samples", light, count);
+        }
+    }
+
     /* -------------------------------------------------------------------------- */
     /*                          WRITE IMAGE DATA TO FILE                          */
     /* -------------------------------------------------------------------------- */
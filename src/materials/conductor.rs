@@ -0,0 +1,144 @@
+use super::microfacet::{fresnel_conductor, reflect, TrowbridgeReitz};
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord},
+        utils::Ray,
+    },
+    utils::{
+        frame::Frame,
+        vec4::{Color, Vec4},
+    },
+};
+use rand::prelude::*;
+
+/// Measured complex refractive indices (eta + i k) of common metals, sampled at red, green and blue wavelengths
+#[derive(Clone, Copy)]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+impl ConductorPreset {
+    /// Returns (eta, k)
+    pub fn complex_refractive_index(&self) -> (Color, Color) {
+        let (eta, k) = match self {
+            ConductorPreset::Gold => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            ConductorPreset::Copper => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            ConductorPreset::Aluminium => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            ConductorPreset::Silver => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+        };
+        (
+            Color::new(eta[0], eta[1], eta[2], 1.),
+            Color::new(k[0], k[1], k[2], 1.),
+        )
+    }
+}
+
+// Rough metal with a GGX microfacet distribution. Reflected directions are drawn from the
+// distribution of visible normals, so the weight of a sample is just Fresnel times the
+// shadowing / masking ratio.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+    tangent_rotation: f32, // in radians
+}
+
+impl Conductor {
+    /// `eta` and `k` are the real and imaginary parts of the refractive index per channel
+    pub fn new(eta: Color, k: Color, roughness: f32) -> Self {
+        Self {
+            eta,
+            k,
+            distribution: TrowbridgeReitz::new(roughness),
+            tangent_rotation: 0.,
+        }
+    }
+
+    pub fn from_preset(preset: ConductorPreset, roughness: f32) -> Self {
+        let (eta, k) = preset.complex_refractive_index();
+        Self::new(eta, k, roughness)
+    }
+
+    /**
+    Different roughness along the surface tangent (u direction) and bitangent (v direction), for
+    brushed metals. The highlight stretches along the rougher direction, across the brush strokes.
+    */
+    pub fn with_anisotropic_roughness(mut self, roughness_x: f32, roughness_y: f32) -> Self {
+        self.distribution = TrowbridgeReitz::anisotropic(roughness_x, roughness_y);
+        self
+    }
+
+    /// Rotates the direction of the anisotropy around the normal, `rotation` is in degrees
+    pub fn with_tangent_rotation(mut self, rotation: f32) -> Self {
+        self.tangent_rotation = rotation.to_radians();
+        self
+    }
+
+    /// Shading frame on the side of the surface the ray comes from, metals are two sided
+    fn local_frame(&self, ray: &Ray, hit_record: &HitRecord) -> Frame {
+        let frame = hit_record.tangent_frame().rotated(self.tangent_rotation);
+        if hit_record.geometric_normal.dot(ray.direction) > 0. {
+            frame.flipped()
+        } else {
+            frame
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = self.local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+
+        let mut rng = rand::thread_rng();
+        let h = self
+            .distribution
+            .sample_visible_normal(wo, rng.gen(), rng.gen());
+        let wi = reflect(-wo, h);
+        if wi.z() <= 0. {
+            return None; // reflected below the surface, the energy is lost to multiple scattering
+        }
+
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let shadowing = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        let mut weight = fresnel * shadowing;
+        weight[3] = 1.;
+
+        Some((
+            weight,
+            Ray::new(
+                hit_record.point_of_intersection,
+                frame.to_world(wi).normalise(),
+            ),
+        ))
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let frame = self.local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+        let wi = frame.to_local(direction.normalise());
+        let h = wo + wi;
+        if wo.z() <= 0. || wi.z() <= 0. || h.is_degenerate() {
+            return Color::new(0., 0., 0., 1.);
+        }
+        let h = h.normalise();
+
+        // F D G / (4 cos_o cos_i), times cos_i
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let mut color =
+            fresnel * (self.distribution.d(h) * self.distribution.g2(wo, wi) / (4. * wo.z()));
+        color[3] = 1.;
+        color
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        let frame = self.local_frame(ray, hit_record);
+        self.distribution.reflection_pdf(
+            frame.to_local(-ray.direction.normalise()),
+            frame.to_local(direction.normalise()),
+        )
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        utils::Ray,
+    },
+    utils::vec4::{Color, Vec4},
+};
+use std::rc::Rc;
+
+/**
+Cuts holes into any material where the red channel of the `opacity` mask is below 1 (eg: leaves,
+fences). Rays go through the surface with a chance of 1 - opacity, so the holes work on every
+object and partly opaque masks give soft edges and shadows.
+*/
+pub struct Cutout {
+    material: Rc<dyn Material>,
+    opacity: Rc<dyn Texture>,
+}
+
+impl Cutout {
+    pub fn new(material: Rc<dyn Material>, opacity: Rc<dyn Texture>) -> Self {
+        Self { material, opacity }
+    }
+}
+
+impl Material for Cutout {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        self.material.generate_reflected_ray(ray, hit_record)
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        self.material.evaluate(ray, hit_record, direction)
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &HitRecord) -> Color {
+        self.material.emitted(ray, hit_record)
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        self.material.pdf(ray, hit_record, direction)
+    }
+
+    fn opacity(&self, ray: &Ray, hit_record: &HitRecord) -> f32 {
+        self.opacity
+            .value(
+                hit_record.u,
+                hit_record.v,
+                &hit_record.point_of_intersection,
+            )
+            .x() *
+            self.material.opacity(ray, hit_record)
+    }
+}
@@ -1,29 +1,196 @@
 use crate::{
     ray_tracer::{
-        interface::{material_base::Material, object_base::HitRecord},
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        medium::{Medium, MediumStack, Scattering},
         utils::Ray,
     },
+    textures::solid_color::SolidColor,
     utils::vec4::{Color, Point, Vec4},
 };
 use rand::prelude::*;
+use std::rc::Rc;
+
+// sodium d-line, the wavelength refractive indices are usually quoted at
+const D_LINE: f32 = 587.6;
+
+/// Wavelength dependent refractive index, coefficients are for wavelengths in micrometres
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    /// n = a + b / λ²
+    Cauchy { a: f32, b: f32 },
+    /// n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    pub const BK7: Self = Self::Sellmeier {
+        b: [1.039_612, 0.231_792_3, 1.010_469],
+        c: [0.006_000_7, 0.020_017_9, 103.560_6],
+    };
+    pub const DIAMOND: Self = Self::Sellmeier {
+        b: [0.3306, 4.3356, 0.],
+        c: [0.030_625, 0.011_236, 0.],
+    };
+
+    /// Refractive index at `wavelength` in nm
+    pub fn refractive_index(&self, wavelength: f32) -> f32 {
+        let lambda_squared = (wavelength / 1000.).powi(2);
+        match self {
+            Self::Cauchy { a, b } => a + b / lambda_squared,
+            Self::Sellmeier { b, c } => {
+                let sum: f32 = (0..3)
+                    .map(|i| b[i] * lambda_squared / (lambda_squared - c[i]))
+                    .sum();
+                (1. + sum).sqrt()
+            }
+        }
+    }
+}
 
 pub struct Dielectric {
-    albedo: Color,
-    // TODO: Support dynamic two way coupling (here the other material is presumed to be air)
+    albedo: Rc<dyn Texture>,
+    // without a priority the other material is presumed to be air
     refractive_index: f32,
+    dispersion: Option<Dispersion>,
+    priority: Option<u32>,
+    scattering: Option<Scattering>,
+    // Beer-Lambert absorption coefficient per unit distance travelled inside, per channel
+    absorption: Color,
 }
 
 impl Dielectric {
     pub fn new(albedo: Color, refractive_index: f32) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(albedo)), refractive_index)
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>, refractive_index: f32) -> Self {
         Self {
             albedo,
             refractive_index,
+            dispersion: None,
+            priority: None,
+            scattering: None,
+            absorption: Color::new(0., 0., 0., 0.),
         }
     }
 
+    /**
+    Makes the refractive index depend on the wavelength. Spectral renders split light into its
+    colours (eg: prisms, diamonds), RGB renders use the refractive index at the sodium d-line.
+    */
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.refractive_index = dispersion.refractive_index(D_LINE);
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    /**
+    Makes the dielectric a nested medium. Rays keep track of the nested media they are in, so
+    refraction uses the refractive index of the medium on the other side of the surface instead
+    of air (eg: water in a glass).
+
+    Where nested media overlap, the one with the highest `priority` wins and the surfaces of the
+    others inside of it are ignored. This lets touching objects overlap slightly instead of
+    having to share the exact same surface.
+    */
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /**
+    Fills the inside with a participating medium, which the engine traces rays through with a
+    random walk (eg: subsurface scattering, murky water). The volume needs the ray to keep track of
+    its media, so this makes the dielectric a nested medium with priority 0 unless it has one.
+    */
+    pub fn with_scattering(mut self, scattering: Scattering) -> Self {
+        self.scattering = Some(scattering);
+        self.priority.get_or_insert(0);
+        self
+    }
+
+    /// Relative refractive index, normal facing the ray and the media of a transmitted ray.
+    /// Err with the media on the other side if the surface is hidden by a medium with a higher priority.
+    fn interface(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        refractive_index: f32,
+    ) -> Result<(f32, Vec4, MediumStack), MediumStack> {
+        let entering = hit_record.geometric_normal.dot(ray.direction) < 0.;
+        // normal maps can tilt the shading normal past the ray, refract off the actual surface then
+        let normal = if (hit_record.normal.dot(ray.direction) < 0.) == entering {
+            hit_record.normal
+        } else {
+            hit_record.geometric_normal
+        };
+
+        let Some(priority) = self.priority else {
+            // Normal adjustment done to keep calculations consistent for refraction for both out and in scenarios
+            return Ok(if entering {
+                // ray coming from outside of object
+                (1. / refractive_index, normal, ray.media.clone())
+            } else {
+                (refractive_index, -normal, ray.media.clone())
+            });
+        };
+
+        let medium = Medium {
+            id: hit_record.object_id,
+            refractive_index,
+            priority,
+            scattering: self.scattering,
+            absorption: self.absorption,
+        };
+        if entering {
+            if ray.media.is_overridden(&medium) {
+                return Err(ray.media.entered(medium));
+            }
+            Ok((
+                ray.media.refractive_index() / refractive_index,
+                normal,
+                ray.media.entered(medium),
+            ))
+        } else {
+            let remaining = ray.media.exited(medium.id);
+            if remaining.is_overridden(&medium) {
+                return Err(remaining);
+            }
+            Ok((
+                refractive_index / remaining.refractive_index(),
+                -normal,
+                remaining,
+            ))
+        }
+    }
+
+    /**
+    Light travelling a distance `d` inside is attenuated by `exp(-absorption * d)`. The engine
+    dims every piece of the path inside, up to whatever surface it reaches next (eg: water or
+    another object in the glass), so this makes the dielectric a nested medium with priority 0
+    unless it has one.
+    */
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self.absorption[3] = 0.;
+        self.priority.get_or_insert(0);
+        self
+    }
+
+    /// Absorption given as the color white light has after travelling `distance` inside (eg: tinted glass)
+    pub fn with_transmittance_at_distance(self, transmittance: Color, distance: f32) -> Self {
+        let coefficient = |channel: f32| -channel.clamp(f32::MIN_POSITIVE, 1.).ln() / distance;
+        self.with_absorption(Color::new(
+            coefficient(transmittance.x()),
+            coefficient(transmittance.y()),
+            coefficient(transmittance.z()),
+            0.,
+        ))
+    }
+
     /// Generates refracted ray
     pub fn refract(normal: Vec4, direction: Point, relative_refractive_index: f32) -> Point {
-        let cos_theta = direction.dot(normal).min(1.) * -1.;
+        let cos_theta = -direction.dot(normal).min(1.);
         let r_out_perpendicular = (direction + normal * cos_theta) * relative_refractive_index;
         let r_out_parallel =
             normal * -1. * (1.0 - r_out_perpendicular.length().powi(2)).abs().sqrt();
@@ -39,42 +206,63 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        // Normal adjustment done to keep calculations consistent for refraction for both out and in scenarios
-        let (relative_refractive_index, adjusted_normal) = if hit_record.normal.dot(ray.direction) < 0. {
-            // ray coming from outside of object
-            (1. / self.refractive_index, hit_record.normal)
-        } else {
-            (self.refractive_index, hit_record.normal * -1.)
+        let albedo = self.albedo.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+
+        // a dispersive path can only carry the hero wavelength on
+        let (refractive_index, wavelengths) = match (self.dispersion, ray.wavelengths) {
+            (Some(dispersion), Some(wavelengths)) => (
+                dispersion.refractive_index(wavelengths.hero()),
+                Some(wavelengths.collapse()),
+            ),
+            _ => (self.refractive_index, ray.wavelengths),
         };
 
+        let (relative_refractive_index, adjusted_normal, transmitted_media) =
+            match self.interface(ray, hit_record, refractive_index) {
+                Ok(interface) => interface,
+                // surface inside of a medium with a higher priority, the ray goes straight through
+                Err(media) => {
+                    return Some((
+                        Color::new(1., 1., 1., 1.),
+                        Ray::new(hit_record.point_of_intersection, ray.direction).with_media(media),
+                    ))
+                }
+            };
+
         // TIR
         let sin_theta = (1. - adjusted_normal.dot(ray.direction).powi(2)).sqrt();
         if relative_refractive_index * sin_theta > 1. {
-            let new_dir = ray.direction - hit_record.normal * ray.direction.dot(hit_record.normal) * 2.;
+            let new_dir = ray.direction - adjusted_normal * ray.direction.dot(adjusted_normal) * 2.;
 
             // TODO: TIR Albedo?
             return Some((
-                self.albedo,
+                albedo,
                 Ray::new(
                     hit_record.point_of_intersection,
                     new_dir,
-                ),
+                )
+                .with_wavelengths(wavelengths),
             ));
         }
 
         // schlick approximation (reflectance)
         let mut rng = rand::thread_rng();
-        let cos_theta = adjusted_normal.dot(ray.direction) * -1.;
+        let cos_theta = -adjusted_normal.dot(ray.direction);
         if rng.gen::<f32>() < Self::reflectance(cos_theta, relative_refractive_index) {
-            let new_dir = ray.direction - hit_record.normal * ray.direction.dot(hit_record.normal) * 2.;
+            let new_dir = ray.direction - adjusted_normal * ray.direction.dot(adjusted_normal) * 2.;
 
             // TODO: Reflectance Albedo?
             return Some((
-                self.albedo,
+                albedo,
                 Ray::new(
                     hit_record.point_of_intersection,
                     new_dir,
-                ),
+                )
+                .with_wavelengths(wavelengths),
             ));
         }
 
@@ -83,11 +271,119 @@ impl Material for Dielectric {
         let refracted_ray_direction =
             Self::refract(adjusted_normal, ray.direction, relative_refractive_index).normalise();
         Some((
-            self.albedo,
+            albedo,
             Ray::new(
                 hit_record.point_of_intersection,
                 refracted_ray_direction,
-            ),
+            )
+            .with_media(transmitted_media)
+            .with_wavelengths(wavelengths),
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::sphere::Sphere;
+    use crate::ray_tracer::interface::object_base::Object;
+
+    const SAMPLE_COUNT: u32 = 10_000;
+
+    /// Hit on the top of a unit sphere made of `material`, the normal points up
+    fn top_of_sphere(material: Dielectric) -> HitRecord {
+        let sphere = Sphere::new(1., Point::new(0., 0., 0., 0.), Rc::new(material));
+        sphere
+            .is_ray_hit(
+                &Ray::new(
+                    Point::new(0., 2., 0., 0.),
+                    Vec4::new(0., -1., 0., 0.),
+                ),
+                0.001,
+                f32::INFINITY,
+            )
+            .unwrap()
+    }
+
+    /// Catalogue values at the F, d and C lines
+    #[test]
+    fn bk7_matches_its_catalogue_indices() {
+        for (wavelength, expected) in [(486.1, 1.5224), (D_LINE, 1.5168), (656.3, 1.5143)] {
+            let refractive_index = Dispersion::BK7.refractive_index(wavelength);
+            assert!(
+                (refractive_index - expected).abs() < 1e-3,
+                "{wavelength} nm: {refractive_index} instead of {expected}"
+            );
+        }
+
+        // usual two term Cauchy fit of BK7
+        let cauchy = Dispersion::Cauchy {
+            a: 1.5046,
+            b: 0.00420,
+        };
+        assert!((cauchy.refractive_index(D_LINE) - 1.5168).abs() < 1e-3);
+    }
+
+    /// The shading normal is tilted so far that it faces away from the ray, the ray still enters
+    #[test]
+    fn entering_is_decided_by_the_geometric_normal() {
+        let mut hit_record =
+            top_of_sphere(Dielectric::new(Color::new(1., 1., 1., 1.), 1.5).with_priority(1));
+        hit_record.normal = Vec4::new(0.9, 0.3, 0., 0.).normalise();
+        let ray = Ray::new(
+            Point::new(-1., 1.3, 0., 0.),
+            Vec4::new(1., -0.3, 0., 0.).normalise(),
+        );
+        assert!(hit_record.normal.dot(ray.direction) > 0.);
+
+        let mut transmitted = 0;
+        for _ in 0..SAMPLE_COUNT {
+            let (_, new_ray) = hit_record
+                .material
+                .generate_reflected_ray(&ray, &hit_record)
+                .unwrap();
+            // reflections stay above the surface, refractions enter the medium
+            if new_ray.direction.dot(hit_record.geometric_normal) < 0. {
+                transmitted += 1;
+                assert!(new_ray.media.current().is_some());
+            }
+        }
+        assert!(transmitted > 0);
+    }
+
+    /// Light going from glass into water bends by the ratio of their indices, not against air
+    #[test]
+    fn refracts_by_the_relative_index_of_nested_media() {
+        let glass = Medium {
+            id: usize::MAX,
+            refractive_index: 1.5,
+            priority: 1,
+            scattering: None,
+            absorption: Color::new(0., 0., 0., 0.),
+        };
+        let hit_record =
+            top_of_sphere(Dielectric::new(Color::new(1., 1., 1., 1.), 1.33).with_priority(2));
+        let sin_theta: f32 = 0.6;
+        let ray = Ray::new(
+            Point::new(-sin_theta, 1.8, 0., 0.),
+            Vec4::new(sin_theta, -0.8, 0., 0.),
+        )
+        .with_media(MediumStack::default().entered(glass));
+
+        let mut transmitted = 0;
+        for _ in 0..SAMPLE_COUNT {
+            let (_, new_ray) = hit_record
+                .material
+                .generate_reflected_ray(&ray, &hit_record)
+                .unwrap();
+            if new_ray.direction.y() < 0. {
+                transmitted += 1;
+                // n₁ sin θ₁ = n₂ sin θ₂
+                let sin_refracted = new_ray.direction.normalise().x();
+                assert!((1.33 * sin_refracted - 1.5 * sin_theta).abs() < 1e-4);
+                assert_eq!(new_ray.media.refractive_index(), 1.33);
+            }
+        }
+        assert!(transmitted > 0);
+    }
+}
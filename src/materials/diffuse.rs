@@ -0,0 +1,457 @@
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        utils::Ray,
+    },
+    textures::solid_color::SolidColor,
+    utils::{
+        frame::Frame,
+        vec4::{Color, Vec4},
+    },
+};
+use std::{f32::consts::PI, rc::Rc};
+
+const BLACK: Color = Color {
+    e: [0., 0., 0., 1.],
+};
+// the albedo of the sheen lobe is tabulated over the cosine of the outgoing direction
+const SHEEN_ALBEDO_SAMPLES: usize = 32;
+const SHEEN_ALBEDO_STEPS: usize = 64; // per axis of the integration over the hemisphere
+
+// Diffuse models beyond Lambertian. They all scatter into the hemisphere on the side of the
+// incoming ray and are sampled cosine weighted, so they only differ in their BRDF, which is
+// evaluated in the local shading frame (normal is +z) for an outgoing `wo` and incoming `wi`.
+
+/// Shading frame on the side of the surface the ray comes from and the outgoing direction in it
+fn local_outgoing(ray: &Ray, hit_record: &HitRecord) -> (Frame, Vec4) {
+    let normal = if hit_record.geometric_normal.dot(ray.direction) > 0. {
+        -hit_record.normal
+    } else {
+        hit_record.normal
+    };
+    let frame = Frame::from_normal(normal);
+    (frame, frame.to_local(-ray.direction.normalise()))
+}
+
+fn sample(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    brdf: impl Fn(Vec4, Vec4) -> Color,
+) -> Option<(Color, Ray)> {
+    let (frame, wo) = local_outgoing(ray, hit_record);
+    let wi = Vec4::random_cosine_direction();
+    if wo.z() <= 0. || wi.z() <= 0. {
+        return None;
+    }
+
+    // brdf * cos / pdf, with the cosine weighted pdf being cos / π
+    let mut weight = brdf(wo, wi) * PI;
+    weight[3] = 1.;
+    Some((
+        weight,
+        Ray::new(
+            hit_record.point_of_intersection,
+            frame.to_world(wi).normalise(),
+        ),
+    ))
+}
+
+fn evaluate(
+    ray: &Ray,
+    hit_record: &HitRecord,
+    direction: Vec4,
+    brdf: impl Fn(Vec4, Vec4) -> Color,
+) -> Color {
+    let (frame, wo) = local_outgoing(ray, hit_record);
+    let wi = frame.to_local(direction.normalise());
+    if wo.z() <= 0. || wi.z() <= 0. {
+        return BLACK;
+    }
+
+    let mut color = brdf(wo, wi) * wi.z();
+    color[3] = 1.;
+    color
+}
+
+fn pdf(ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+    let (frame, _) = local_outgoing(ray, hit_record);
+    frame.to_local(direction.normalise()).z().max(0.) / PI
+}
+
+fn albedo(texture: &Rc<dyn Texture>, hit_record: &HitRecord) -> Color {
+    texture.value(
+        hit_record.u,
+        hit_record.v,
+        &hit_record.point_of_intersection,
+    )
+}
+
+/**
+Oren-Nayar diffuse, for rough surfaces made of tiny lambertian facets (eg: clay, concrete). Rougher
+surfaces look flatter than Lambertian ones, since they stay bright towards their silhouette.
+
+- `sigma`: standard deviation of the facet angles in degrees, 0 is Lambertian
+*/
+pub struct OrenNayar {
+    albedo: Rc<dyn Texture>,
+    a: f32,
+    b: f32,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f32) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(albedo)), sigma)
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>, sigma: f32) -> Self {
+        let sigma_squared = sigma.to_radians().powi(2);
+        Self {
+            albedo,
+            a: 1. - sigma_squared / (2. * (sigma_squared + 0.33)),
+            b: 0.45 * sigma_squared / (sigma_squared + 0.09),
+        }
+    }
+
+    fn brdf(&self, albedo: Color, wo: Vec4, wi: Vec4) -> Color {
+        let sin_o = (1. - wo.z() * wo.z()).max(0.).sqrt();
+        let sin_i = (1. - wi.z() * wi.z()).max(0.).sqrt();
+
+        // cosine of the azimuth between the two directions
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            ((wo.x() * wi.x() + wo.y() * wi.y()) / (sin_o * sin_i)).max(0.)
+        } else {
+            0.
+        };
+
+        // sin(max(θi, θo)) * tan(min(θi, θo))
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() {
+            (sin_o, sin_i / wi.z())
+        } else {
+            (sin_i, sin_o / wo.z())
+        };
+
+        albedo * ((self.a + self.b * cos_phi * sin_alpha * tan_beta) / PI)
+    }
+}
+
+impl Material for OrenNayar {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let albedo = albedo(&self.albedo, hit_record);
+        sample(ray, hit_record, |wo, wi| {
+            self.brdf(albedo, wo, wi)
+        })
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let albedo = albedo(&self.albedo, hit_record);
+        evaluate(ray, hit_record, direction, |wo, wi| {
+            self.brdf(albedo, wo, wi)
+        })
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        pdf(ray, hit_record, direction)
+    }
+}
+
+/**
+Burley's (Disney) diffuse. Rough surfaces get brighter when lit and viewed from the same grazing
+direction (retro-reflection, eg: dusty or fibrous surfaces), smooth ones get darker edges.
+
+- `roughness`: in [0, 1]
+*/
+pub struct RetroReflectiveDiffuse {
+    albedo: Rc<dyn Texture>,
+    roughness: f32,
+}
+
+impl RetroReflectiveDiffuse {
+    pub fn new(albedo: Color, roughness: f32) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(albedo)), roughness)
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>, roughness: f32) -> Self {
+        Self {
+            albedo,
+            roughness: roughness.clamp(0., 1.),
+        }
+    }
+
+    fn brdf(&self, albedo: Color, wo: Vec4, wi: Vec4) -> Color {
+        let cos_d = wi.dot((wo + wi).normalise());
+        let grazing = 0.5 + 2. * self.roughness * cos_d * cos_d;
+        let schlick = |cos_theta: f32| 1. + (grazing - 1.) * (1. - cos_theta).powi(5);
+
+        albedo * (schlick(wo.z()) * schlick(wi.z()) / PI)
+    }
+}
+
+impl Material for RetroReflectiveDiffuse {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let albedo = albedo(&self.albedo, hit_record);
+        sample(ray, hit_record, |wo, wi| {
+            self.brdf(albedo, wo, wi)
+        })
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let albedo = albedo(&self.albedo, hit_record);
+        evaluate(ray, hit_record, direction, |wo, wi| {
+            self.brdf(albedo, wo, wi)
+        })
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        pdf(ray, hit_record, direction)
+    }
+}
+
+/**
+Cloth, a lambertian base with a velvet sheen on top that catches the light at grazing angles
+(Estevez and Kulla's "Charlie" sheen distribution with Neubelt's visibility term). Light reflected
+by the sheen does not reach the base, so the base is dimmed by the albedo of the sheen.
+
+- `sheen`: color of the sheen, black turns it off
+- `roughness`: in [0, 1], how far from the grazing angles the sheen spreads
+*/
+pub struct Sheen {
+    albedo: Rc<dyn Texture>,
+    sheen: Color,
+    roughness: f32,
+    sheen_albedo: Vec<f32>, // of a white sheen, for cos_o from 0 to 1
+}
+
+impl Sheen {
+    pub fn new(albedo: Color, sheen: Color, roughness: f32) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(albedo)), sheen, roughness)
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>, sheen: Color, roughness: f32) -> Self {
+        let roughness = roughness.clamp(0.05, 1.);
+        let sheen_albedo = (0..SHEEN_ALBEDO_SAMPLES)
+            .map(|i| {
+                let cos_theta = i as f32 / (SHEEN_ALBEDO_SAMPLES - 1) as f32;
+                Self::integrate_sheen_albedo(roughness, cos_theta)
+            })
+            .collect();
+
+        Self {
+            albedo,
+            sheen,
+            roughness,
+            sheen_albedo,
+        }
+    }
+
+    /// Sheen brdf of a white sheen
+    fn sheen_lobe(roughness: f32, wo: Vec4, wi: Vec4) -> f32 {
+        let h = (wo + wi).normalise();
+        let sin_h = (1. - h.z() * h.z()).max(0.).sqrt();
+        let distribution = (2. + 1. / roughness) * sin_h.powf(1. / roughness) / (2. * PI);
+        let visibility = 1. / (4. * (wi.z() + wo.z() - wi.z() * wo.z()));
+        distribution * visibility
+    }
+
+    /// Fraction of the light reflected towards `wo` by a white sheen, midpoint rule over the hemisphere
+    fn integrate_sheen_albedo(roughness: f32, cos_theta_o: f32) -> f32 {
+        let wo = Vec4::new(
+            (1. - cos_theta_o * cos_theta_o).sqrt(),
+            0.,
+            cos_theta_o,
+            0.,
+        );
+        // symmetric around the plane of wo, half of the azimuths are enough
+        let (d_theta, d_phi) = (
+            PI / 2. / SHEEN_ALBEDO_STEPS as f32,
+            PI / SHEEN_ALBEDO_STEPS as f32,
+        );
+        let mut sum = 0.;
+        for i in 0..SHEEN_ALBEDO_STEPS {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..SHEEN_ALBEDO_STEPS {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let wi = Vec4::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    0.,
+                );
+                sum += Self::sheen_lobe(roughness, wo, wi) * wi.z() * theta.sin();
+            }
+        }
+        2. * sum * d_theta * d_phi
+    }
+
+    fn sheen_albedo(&self, cos_theta_o: f32) -> f32 {
+        let x = cos_theta_o.clamp(0., 1.) * (SHEEN_ALBEDO_SAMPLES - 1) as f32;
+        let i = (x as usize).min(SHEEN_ALBEDO_SAMPLES - 2);
+        let t = x - i as f32;
+        self.sheen_albedo[i] * (1. - t) + self.sheen_albedo[i + 1] * t
+    }
+
+    fn brdf(&self, albedo: Color, wo: Vec4, wi: Vec4) -> Color {
+        // the visibility term makes very smooth sheens reflect more than they receive at grazing
+        // angles, those are scaled down to reflect everything
+        let sheen_albedo = self.sheen_albedo(wo.z());
+        let sheen = Self::sheen_lobe(self.roughness, wo, wi) / sheen_albedo.max(1.);
+
+        let mut color = BLACK;
+        for channel in 0..3 {
+            let base_scale = 1. - self.sheen[channel] * sheen_albedo.min(1.);
+            color[channel] = albedo[channel] / PI * base_scale + self.sheen[channel] * sheen;
+        }
+        color
+    }
+}
+
+impl Material for Sheen {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let albedo = albedo(&self.albedo, hit_record);
+        sample(ray, hit_record, |wo, wi| {
+            self.brdf(albedo, wo, wi)
+        })
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let albedo = albedo(&self.albedo, hit_record);
+        evaluate(ray, hit_record, direction, |wo, wi| {
+            self.brdf(albedo, wo, wi)
+        })
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        pdf(ray, hit_record, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::vec4::Point;
+
+    const SAMPLE_COUNT: u32 = 20_000;
+    const TOLERANCE: f32 = 0.02;
+
+    fn hit_record(material: Rc<dyn Material>) -> HitRecord {
+        HitRecord {
+            normal: Vec4::new(0., 1., 0., 0.),
+            geometric_normal: Vec4::new(0., 1., 0., 0.),
+            point_of_intersection: Point::new(0., 0., 0., 0.),
+            t: 1.,
+            u: 0.5,
+            v: 0.5,
+            dpdu: Vec4::new(1., 0., 0., 0.),
+            dpdv: Vec4::new(0., 0., 1., 0.),
+            material,
+            object_id: 0,
+            normal_map: None,
+        }
+    }
+
+    fn incoming_ray(cos_theta: f32) -> Ray {
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        Ray::new(
+            Point::new(-sin_theta, cos_theta, 0., 0.),
+            Vec4::new(sin_theta, -cos_theta, 0., 0.),
+        )
+    }
+
+    fn materials() -> Vec<Rc<dyn Material>> {
+        let white = Color::new(1., 1., 1., 1.);
+        vec![
+            Rc::new(OrenNayar::new(white, 30.)),
+            Rc::new(RetroReflectiveDiffuse::new(white, 0.8)),
+            Rc::new(Sheen::new(white * 0.5, white * 0.5, 0.3)),
+        ]
+    }
+
+    #[test]
+    fn sampled_weights_match_evaluate_over_pdf() {
+        for material in materials() {
+            let hit_record = hit_record(Rc::clone(&material));
+            for cos_theta in [1., 0.5, 0.1] {
+                let ray = incoming_ray(cos_theta);
+                for _ in 0..100 {
+                    let (weight, new_ray) =
+                        material.generate_reflected_ray(&ray, &hit_record).unwrap();
+                    let expected = material.evaluate(&ray, &hit_record, new_ray.direction) /
+                        material.pdf(&ray, &hit_record, new_ray.direction);
+                    for channel in 0..3 {
+                        assert!((weight[channel] - expected[channel]).abs() < 1e-3);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn white_sheen_over_white_base_reflects_everything() {
+        let white = Color::new(1., 1., 1., 1.);
+        for roughness in [0.05, 0.3, 1.] {
+            let material: Rc<dyn Material> = Rc::new(Sheen::new(white, white, roughness));
+            let hit_record = hit_record(Rc::clone(&material));
+            for cos_theta in [1., 0.5, 0.1] {
+                let ray = incoming_ray(cos_theta);
+                // the smooth sheen at grazing angles is noisy under cosine sampling
+                let sample_count = 10 * SAMPLE_COUNT;
+                let mut sum = Color::new(0., 0., 0., 0.);
+                for _ in 0..sample_count {
+                    sum += material
+                        .generate_reflected_ray(&ray, &hit_record)
+                        .unwrap()
+                        .0;
+                }
+                let albedo = sum / sample_count as f32;
+                assert!(
+                    (albedo.x() - 1.).abs() < TOLERANCE,
+                    "roughness {}, cos_theta {}: albedo {:?}",
+                    roughness,
+                    cos_theta,
+                    albedo.e
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_white_oren_nayar_reflects_everything() {
+        let material: Rc<dyn Material> = Rc::new(OrenNayar::new(Color::new(1., 1., 1., 1.), 0.));
+        let hit_record = hit_record(Rc::clone(&material));
+        for cos_theta in [1., 0.5, 0.1] {
+            let ray = incoming_ray(cos_theta);
+            let mut sum = Color::new(0., 0., 0., 0.);
+            for _ in 0..SAMPLE_COUNT {
+                sum += material
+                    .generate_reflected_ray(&ray, &hit_record)
+                    .unwrap()
+                    .0;
+            }
+            let albedo = sum / SAMPLE_COUNT as f32;
+            assert!(
+                (albedo.x() - 1.).abs() < TOLERANCE,
+                "albedo {:?}",
+                albedo.e
+            );
+        }
+    }
+
+    #[test]
+    fn rough_white_oren_nayar_loses_some_energy() {
+        let material: Rc<dyn Material> = Rc::new(OrenNayar::new(Color::new(1., 1., 1., 1.), 40.));
+        let hit_record = hit_record(Rc::clone(&material));
+        let ray = incoming_ray(1.);
+        let mut sum = Color::new(0., 0., 0., 0.);
+        for _ in 0..SAMPLE_COUNT {
+            sum += material
+                .generate_reflected_ray(&ray, &hit_record)
+                .unwrap()
+                .0;
+        }
+        let albedo = sum / SAMPLE_COUNT as f32;
+        assert!(
+            albedo.x() < 1. && albedo.x() > 0.7,
+            "albedo {:?}",
+            albedo.e
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        spectrum::{blackbody, spectrum_to_rgb},
+        utils::Ray,
+    },
+    textures::solid_color::SolidColor,
+    utils::vec4::Color,
+};
+use std::rc::Rc;
+
+// enough for the smooth blackbody spectrum
+const BLACKBODY_SAMPLES: usize = 32;
+
+enum Emission {
+    Texture(Rc<dyn Texture>, f32),
+    Blackbody {
+        temperature: f32,
+        scale: f32, // applied to Planck's law
+        color: Color,
+    },
+}
+
+/**
+Surface that gives off light (eg: lamps, screens, fire) and does not reflect any. Light is only
+emitted from the front of the surface, the side the normal points to.
+*/
+pub struct Emissive {
+    emission: Emission,
+}
+
+impl Emissive {
+    pub fn new(color: Color, intensity: f32) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(color)), intensity)
+    }
+
+    pub fn from_texture(color: Rc<dyn Texture>, intensity: f32) -> Self {
+        Self {
+            emission: Emission::Texture(color, intensity),
+        }
+    }
+
+    /**
+    Color of a blackbody at `temperature` (in K, eg: 2700 for a tungsten bulb, 6500 for daylight),
+    with its luminance set to `intensity`. Only the hue follows the temperature.
+    */
+    pub fn blackbody(temperature: f32, intensity: f32) -> Self {
+        let color = Self::blackbody_color(temperature, 1.);
+        Self::blackbody_physical(
+            temperature,
+            intensity / color.luminance().max(f32::MIN_POSITIVE),
+        )
+    }
+
+    /**
+    Blackbody with physical intensity, Planck's law scaled by `scale`. Radiance is in units where
+    daylight of 1 W / (sr m² nm) at 560 nm is white 1, so hotter bodies are a lot brighter.
+    */
+    pub fn blackbody_physical(temperature: f32, scale: f32) -> Self {
+        Self {
+            emission: Emission::Blackbody {
+                temperature,
+                scale,
+                color: Self::blackbody_color(temperature, scale),
+            },
+        }
+    }
+
+    fn blackbody_color(temperature: f32, scale: f32) -> Color {
+        spectrum_to_rgb(
+            |wavelength| blackbody(wavelength, temperature) * scale,
+            BLACKBODY_SAMPLES,
+        )
+    }
+}
+
+impl Material for Emissive {
+    fn generate_reflected_ray(&self, _ray: &Ray, _hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &HitRecord) -> Color {
+        if hit_record.geometric_normal.dot(ray.direction) >= 0. {
+            return Color::new(0., 0., 0., 1.);
+        }
+
+        let mut emitted = match &self.emission {
+            Emission::Texture(texture, intensity) => {
+                let color = texture.value(
+                    hit_record.u,
+                    hit_record.v,
+                    &hit_record.point_of_intersection,
+                ) * *intensity;
+                match ray.wavelengths {
+                    Some(wavelengths) => wavelengths.upsample_light(color),
+                    None => color,
+                }
+            }
+            // spectral rays get the exact spectrum, not an upsampled RGB color
+            Emission::Blackbody {
+                temperature,
+                scale,
+                color,
+            } => match ray.wavelengths {
+                Some(wavelengths) => {
+                    wavelengths.evaluate(|wavelength| blackbody(wavelength, *temperature) * scale)
+                }
+                None => *color,
+            },
+        };
+        emitted[3] = 1.;
+        emitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::sphere::Sphere;
+    use crate::ray_tracer::{interface::object_base::Object, spectrum::Wavelengths};
+    use crate::utils::vec4::{Point, Vec4};
+
+    const SAMPLE_COUNT: usize = 1000;
+
+    /// RGB and average spectral radiance (back to RGB) of a unit sphere made of `material`, seen from outside
+    fn emitted_rgb_and_spectral(material: Emissive) -> (Color, Color) {
+        let sphere = Sphere::new(1., Point::new(0., 0., 0., 0.), Rc::new(material));
+        let ray = Ray::new(
+            Point::new(0., 2., 0., 0.),
+            Vec4::new(0., -1., 0., 0.),
+        );
+        let hit_record = sphere.is_ray_hit(&ray, 0.001, f32::INFINITY).unwrap();
+        let rgb = hit_record.material.emitted(&ray, &hit_record);
+
+        let mut spectral = Color::new(0., 0., 0., 0.);
+        for sample in 0..SAMPLE_COUNT {
+            let wavelengths = Wavelengths::sample((sample as f32 + 0.5) / SAMPLE_COUNT as f32);
+            let ray = ray.clone().with_wavelengths(Some(wavelengths));
+            spectral += wavelengths.to_rgb(hit_record.material.emitted(&ray, &hit_record)) /
+                SAMPLE_COUNT as f32;
+        }
+        (rgb, spectral)
+    }
+
+    /// Blackbody color with its brightest channel at 1
+    fn normalised_color(temperature: f32) -> Color {
+        let color = Emissive::blackbody_color(temperature, 1.);
+        color / color.x().max(color.y()).max(color.z())
+    }
+
+    #[test]
+    fn daylight_blackbody_is_white() {
+        let color = normalised_color(6500.);
+        for channel in 0..3 {
+            assert!(
+                color[channel] > 0.98,
+                "{:?}",
+                [color.x(), color.y(), color.z()]
+            );
+        }
+    }
+
+    #[test]
+    fn hotter_blackbodies_are_bluer() {
+        let temperatures = [1500., 2700., 4000., 6500., 10000.];
+        for pair in temperatures.windows(2) {
+            let (cooler, hotter) = (
+                normalised_color(pair[0]),
+                normalised_color(pair[1]),
+            );
+            assert!(
+                hotter.z() / hotter.x() > cooler.z() / cooler.x(),
+                "{pair:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn spectral_emission_matches_rgb() {
+        for material in [
+            Emissive::new(Color::new(1., 0.5, 0.2, 1.), 2.),
+            Emissive::blackbody(3000., 2.),
+            Emissive::blackbody_physical(6500., 1e-7),
+        ] {
+            let (rgb, spectral) = emitted_rgb_and_spectral(material);
+            let brightest = rgb.x().max(rgb.y()).max(rgb.z());
+            for channel in 0..3 {
+                assert!(
+                    (spectral[channel] - rgb[channel]).abs() < 0.03 * brightest,
+                    "channel {channel}: {} spectrally, {} in RGB",
+                    spectral[channel],
+                    rgb[channel]
+                );
+            }
+        }
+    }
+}
@@ -1,32 +1,105 @@
-use crate::ray_tracer::interface::object_base::HitRecord;
+use crate::ray_tracer::interface::{object_base::HitRecord, texture_base::Texture};
 use crate::ray_tracer::{interface::material_base::Material, utils::Ray};
-use crate::utils::vec4::{Color, Point};
+use crate::textures::solid_color::SolidColor;
+use crate::utils::{
+    frame::Frame,
+    vec4::{Color, Vec4},
+};
+use std::{f32::consts::PI, rc::Rc};
 
 pub struct Lambertian {
-    albedo: Color, // the % of r,g,b the material will reflect
+    albedo: Rc<dyn Texture>, // the % of r,g,b the material will reflect
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        let random_unit = Point::random_in_unit_sphere(); // picking point on a sphere
-        let new_dir = random_unit.normalise() + hit_record.normal;
-
+    fn generate_reflected_ray(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
         let new_ray = Ray::new(
             hit_record.point_of_intersection,
-            if new_dir.is_degenerate() {
-                ray.direction
-            } else {
-                new_dir.normalise()
-            },
+            Frame::from_normal(hit_record.normal)
+                .to_world(Vec4::random_cosine_direction())
+                .normalise(),
         );
 
         // TODO: copies vec4 here (can we avoid this?)
-        Some((self.albedo * new_ray.direction.dot(hit_record.normal), new_ray))
+        let albedo = self.albedo.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+        // directions are cosine weighted, so the brdf (albedo / π) times the cosine over the pdf
+        // (cosine / π) leaves the albedo, the same light as `evaluate` gives
+        Some((albedo, new_ray))
+    }
+
+    fn evaluate(&self, _ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let cos_theta = direction.normalise().dot(hit_record.normal);
+        if cos_theta <= 0. {
+            return Color::new(0., 0., 0., 1.);
+        }
+
+        let albedo = self.albedo.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+        let mut color = albedo * (cos_theta / PI);
+        color[3] = 1.;
+        color
+    }
+
+    fn pdf(&self, _ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        direction.normalise().dot(hit_record.normal).max(0.) / PI
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::sphere::Sphere;
+    use crate::ray_tracer::interface::object_base::Object;
+    use crate::utils::vec4::Point;
+
+    const SAMPLE_COUNT: u32 = 100_000;
+
+    #[test]
+    fn samples_are_cosine_weighted() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::new(Color::new(1., 1., 1., 1.)));
+        let sphere = Sphere::new(1., Point::new(0., 0., 0., 0.), material);
+        let ray = Ray::new(
+            Point::new(0., 2., 0., 0.),
+            Vec4::new(0., -1., 0., 0.),
+        );
+        let hit_record = sphere.is_ray_hit(&ray, 0.001, f32::INFINITY).unwrap();
+
+        // moments of the cosine with the normal, 2 / 3 and 1 / 2 for a density of cos / π
+        let (mut cos_sum, mut cos_squared_sum) = (0., 0.);
+        for _ in 0..SAMPLE_COUNT {
+            let (weight, new_ray) = hit_record
+                .material
+                .generate_reflected_ray(&ray, &hit_record)
+                .unwrap();
+            assert_eq!(weight.e, [1., 1., 1., 1.]);
+            let cos_theta = new_ray.direction.dot(hit_record.normal);
+            cos_sum += cos_theta;
+            cos_squared_sum += cos_theta * cos_theta;
+        }
+        let cos_mean = cos_sum / SAMPLE_COUNT as f32;
+        let cos_squared_mean = cos_squared_sum / SAMPLE_COUNT as f32;
+        assert!((cos_mean - 2. / 3.).abs() < 0.01, "{}", cos_mean);
+        assert!(
+            (cos_squared_mean - 0.5).abs() < 0.01,
+            "{}",
+            cos_squared_mean
+        );
     }
 }
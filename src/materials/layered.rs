@@ -0,0 +1,383 @@
+use super::{
+    dielectric::Dielectric,
+    microfacet::{fresnel_dielectric, reflect},
+    thin_film::thin_film_reflectance,
+};
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord},
+        utils::Ray,
+    },
+    utils::vec4::{Color, Vec4},
+};
+use rand::prelude::*;
+use std::rc::Rc;
+
+// light bouncing between the coat and the base more often than this is treated as absorbed
+const MAX_INTERNAL_BOUNCES: u32 = 16;
+
+/// Light going into the coat, off the base and back out, see `Layered::through_coat`
+struct CoatPath {
+    inner_ray: Ray,
+    inner_direction: Vec4, // towards the light, inside of the coat
+    outgoing_reflectance: Color,
+    incoming_reflectance: Color,
+    jacobian: f32, // change of solid angle from inside to outside of the coat, cos_i / (η² cos_i')
+}
+
+/**
+Smooth dielectric clearcoat stacked over any base material (eg: car paint, varnished wood).
+
+Light is either reflected off the coat (Fresnel) or refracted into it, where it bounces off the
+base and tries to leave through the coat again. Light that is reflected back down at the coat
+(inner Fresnel and total internal reflection) hits the base again, so the coat darkens and
+saturates the base the way a real coating does. The coat is infinitely thin and only covers the
+front of the surface.
+*/
+pub struct Layered {
+    base: Rc<dyn Material>,
+    coat_refractive_index: f32,
+    thin_film: Option<(f32, f32)>, // thickness in nm, refractive index
+}
+
+impl Layered {
+    pub fn new(base: Rc<dyn Material>, coat_refractive_index: f32) -> Self {
+        Self {
+            base,
+            coat_refractive_index,
+            thin_film: None,
+        }
+    }
+
+    /// Adds a thin film on top of the coat, which makes its reflection iridescent (eg: oil on water)
+    pub fn with_thin_film(mut self, thickness: f32, refractive_index: f32) -> Self {
+        self.thin_film = Some((thickness, refractive_index));
+        self
+    }
+
+    fn coat_reflectance(&self, cos_theta: f32) -> Color {
+        match self.thin_film {
+            Some((thickness, refractive_index)) => thin_film_reflectance(
+                cos_theta,
+                thickness,
+                refractive_index,
+                self.coat_refractive_index,
+            ),
+            None => {
+                let fresnel = fresnel_dielectric(cos_theta, self.coat_refractive_index);
+                Color::new(fresnel, fresnel, fresnel, 1.)
+            }
+        }
+    }
+
+    /// Reflectance of the coat for light hitting it from below at `cos_theta`. Like a plain
+    /// interface, a thin film reflects the same from both sides (for the matching refracted angle).
+    fn inner_coat_reflectance(&self, cos_theta: f32) -> Color {
+        let sin_theta_outside_squared =
+            (1. - cos_theta * cos_theta) * self.coat_refractive_index.powi(2);
+        if sin_theta_outside_squared >= 1. {
+            return Color::new(1., 1., 1., 1.); // TIR
+        }
+        self.coat_reflectance((1. - sin_theta_outside_squared).sqrt())
+    }
+
+    /**
+    Picks between reflecting off the coat and going through it, proportional to the average
+    reflectance.
+
+    Returns:
+
+    Whether the light was reflected, and the weight of that pick
+    */
+    fn pick_reflection(reflectance: Color) -> (bool, Color) {
+        let reflect_probability = Self::reflect_probability(reflectance);
+        if rand::thread_rng().gen::<f32>() < reflect_probability {
+            let mut weight = reflectance / reflect_probability;
+            weight[3] = 1.;
+            return (true, weight);
+        }
+
+        let weight = Color::new(
+            (1. - reflectance.x()) / (1. - reflect_probability),
+            (1. - reflectance.y()) / (1. - reflect_probability),
+            (1. - reflectance.z()) / (1. - reflect_probability),
+            1.,
+        );
+        (false, weight)
+    }
+
+    fn reflect_probability(reflectance: Color) -> f32 {
+        ((reflectance.x() + reflectance.y() + reflectance.z()) / 3.).clamp(0.001, 0.999)
+    }
+
+    /// Path of light from `direction` through the coat, off the base and back out along the ray.
+    /// Light bouncing between the coat and the base more than once is left to sampling.
+    fn through_coat(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Option<CoatPath> {
+        let normal = hit_record.normal;
+        let outgoing = ray.direction.normalise();
+        let direction = direction.normalise();
+        let cos_theta_i = direction.dot(normal);
+        if cos_theta_i <= 0. {
+            return None;
+        }
+
+        let inner_ray = Ray::new(
+            hit_record.point_of_intersection,
+            Dielectric::refract(normal, outgoing, 1. / self.coat_refractive_index).normalise(),
+        )
+        .with_media(ray.media.clone())
+        .with_wavelengths(ray.wavelengths);
+        let inner_direction = -Dielectric::refract(
+            normal,
+            -direction,
+            1. / self.coat_refractive_index,
+        )
+        .normalise();
+
+        Some(CoatPath {
+            inner_ray,
+            inner_direction,
+            outgoing_reflectance: self.coat_reflectance(-outgoing.dot(normal)),
+            incoming_reflectance: self.coat_reflectance(cos_theta_i),
+            jacobian: cos_theta_i /
+                (self.coat_refractive_index.powi(2) * inner_direction.dot(normal)),
+        })
+    }
+}
+
+impl Material for Layered {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let normal = hit_record.normal;
+        let direction = ray.direction.normalise();
+        if direction.dot(normal) >= 0. {
+            return self.base.generate_reflected_ray(ray, hit_record);
+        }
+
+        let point = hit_record.point_of_intersection;
+
+        // reflect off the coat or refract into it
+        let (reflected, mut weight) =
+            Self::pick_reflection(self.coat_reflectance(-direction.dot(normal)));
+        if reflected {
+            // a discrete direction, `pdf` only gives densities for light through the coat
+            return Some((
+                weight,
+                Ray::new(point, reflect(direction, normal)).with_scattering_pdf(Some(0.)),
+            ));
+        }
+
+        let mut inner_direction =
+            Dielectric::refract(normal, direction, 1. / self.coat_refractive_index).normalise();
+
+        for bounce in 0..MAX_INTERNAL_BOUNCES {
+            let inner_ray = Ray::new(point, inner_direction)
+                .with_media(ray.media.clone())
+                .with_wavelengths(ray.wavelengths);
+            let (attenuation, base_ray) =
+                self.base.generate_reflected_ray(&inner_ray, hit_record)?;
+            weight *= attenuation;
+
+            // transmitted through the base, the coat is not in the way
+            let up = base_ray.direction.normalise();
+            let cos_theta = up.dot(normal);
+            if cos_theta <= 0. {
+                return Some((weight, base_ray));
+            }
+
+            // leave through the coat or get reflected back down to the base
+            let (reflected, pick_weight) =
+                Self::pick_reflection(self.inner_coat_reflectance(cos_theta));
+            weight *= pick_weight;
+            if !reflected {
+                // `pdf` and `evaluate` only cover light going through the coat once each way, so
+                // lights are not weighed against light that bounced inside more often. Discrete
+                // directions of the base stay discrete, its densities are for the inside.
+                let scattering_pdf = match bounce {
+                    0 => base_ray.scattering_pdf.filter(|pdf| *pdf <= 0.),
+                    _ => Some(0.),
+                };
+                let outgoing = Dielectric::refract(-normal, up, self.coat_refractive_index);
+                return Some((
+                    weight,
+                    Ray::new(point, outgoing.normalise())
+                        .with_media(base_ray.media)
+                        .with_wavelengths(base_ray.wavelengths)
+                        .with_scattering_pdf(scattering_pdf),
+                ));
+            }
+            inner_direction = reflect(up, normal);
+        }
+
+        None
+    }
+
+    // the coat reflection is discrete, only the base lights the surface through the coat
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        if ray.direction.dot(hit_record.geometric_normal) >= 0. {
+            return self.base.evaluate(ray, hit_record, direction);
+        }
+        let Some(path) = self.through_coat(ray, hit_record, direction) else {
+            return Color::new(0., 0., 0., 1.);
+        };
+
+        let base = self
+            .base
+            .evaluate(&path.inner_ray, hit_record, path.inner_direction);
+        let mut color = Color::new(0., 0., 0., 1.);
+        for channel in 0..3 {
+            color[channel] = base[channel] *
+                (1. - path.outgoing_reflectance[channel]) *
+                (1. - path.incoming_reflectance[channel]) *
+                path.jacobian;
+        }
+        color
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        if ray.direction.dot(hit_record.geometric_normal) >= 0. {
+            return self.base.pdf(ray, hit_record, direction);
+        }
+        let Some(path) = self.through_coat(ray, hit_record, direction) else {
+            return 0.;
+        };
+
+        self.base
+            .pdf(&path.inner_ray, hit_record, path.inner_direction) *
+            (1. - Self::reflect_probability(path.outgoing_reflectance)) *
+            (1. - Self::reflect_probability(path.incoming_reflectance)) *
+            path.jacobian
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::{lambertian::Lambertian, metal::Metal, principled::Principled},
+        utils::vec4::{Point, Vec4},
+    };
+
+    const SAMPLE_COUNT: u32 = 20_000;
+    const TOLERANCE: f32 = 0.02;
+
+    /// White furnace: average weight of the scattered rays for light coming in at `cos_theta`
+    fn albedo(material: Layered, cos_theta: f32) -> Color {
+        let material: Rc<dyn Material> = Rc::new(material);
+        let hit_record = HitRecord {
+            normal: Vec4::new(0., 1., 0., 0.),
+            geometric_normal: Vec4::new(0., 1., 0., 0.),
+            point_of_intersection: Point::new(0., 0., 0., 0.),
+            t: 1.,
+            u: 0.5,
+            v: 0.5,
+            dpdu: Vec4::new(1., 0., 0., 0.),
+            dpdv: Vec4::new(0., 0., 1., 0.),
+            material: Rc::clone(&material),
+            object_id: 0,
+            normal_map: None,
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let ray = Ray::new(
+            Point::new(-sin_theta, cos_theta, 0., 0.),
+            Vec4::new(sin_theta, -cos_theta, 0., 0.),
+        );
+
+        let mut sum = Color::new(0., 0., 0., 0.);
+        for _ in 0..SAMPLE_COUNT {
+            if let Some((weight, new_ray)) = material.generate_reflected_ray(&ray, &hit_record) {
+                assert!(weight.e.iter().all(|c| c.is_finite() && *c >= 0.));
+                assert!(new_ray.direction.dot(hit_record.normal) > 0.);
+                sum += weight;
+            }
+        }
+        sum / SAMPLE_COUNT as f32
+    }
+
+    fn assert_close(albedo: Color, expected: f32) {
+        for channel in 0..3 {
+            assert!(
+                (albedo[channel] - expected).abs() < TOLERANCE,
+                "albedo {:?}, expected {}",
+                albedo.e,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn coat_over_white_diffuse_loses_no_energy() {
+        let base = Rc::new(Principled::new(Color::new(1., 1., 1., 1.)).with_specular(0., 0.));
+        for cos_theta in [1., 0.5, 0.1] {
+            assert_close(
+                albedo(Layered::new(base.clone(), 1.5), cos_theta),
+                1.,
+            );
+        }
+    }
+
+    #[test]
+    fn coat_over_mirror_loses_no_energy() {
+        let base = Rc::new(Metal::new(Color::new(1., 1., 1., 1.), None));
+        for cos_theta in [1., 0.5, 0.1] {
+            assert_close(
+                albedo(
+                    Layered::new(base.clone(), 1.5).with_thin_film(300., 1.33),
+                    cos_theta,
+                ),
+                1.,
+            );
+        }
+    }
+
+    /// Light inside of the coat bounces between the base and the coat, leaving with a chance of
+    /// albedo * (1 - inner reflectance) per bounce
+    #[test]
+    fn thin_film_coat_over_grey_diffuse_matches_closed_form() {
+        let base_albedo = 0.5;
+        let coat = || {
+            Layered::new(
+                Rc::new(Lambertian::new(Color::new(
+                    base_albedo,
+                    base_albedo,
+                    base_albedo,
+                    1.,
+                ))),
+                1.5,
+            )
+            .with_thin_film(400., 2.4)
+        };
+
+        // inner reflectance averaged over the cosine weighted directions leaving the base
+        let steps = 1000;
+        let mut inner = Color::new(0., 0., 0., 0.);
+        for step in 0..steps {
+            let cos_theta = (step as f32 + 0.5) / steps as f32;
+            inner += coat().inner_coat_reflectance(cos_theta) * (2. * cos_theta / steps as f32);
+        }
+
+        for cos_theta in [1., 0.5] {
+            let albedo = albedo(coat(), cos_theta);
+            let reflectance = coat().coat_reflectance(cos_theta);
+            for channel in 0..3 {
+                let (r, inner) = (reflectance[channel], inner[channel]);
+                let expected =
+                    r + (1. - r) * base_albedo * (1. - inner) / (1. - base_albedo * inner);
+                assert!(
+                    (albedo[channel] - expected).abs() < TOLERANCE,
+                    "albedo {:?}, expected {} in channel {}",
+                    albedo.e,
+                    expected,
+                    channel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn thin_film_of_no_thickness_is_plain_fresnel() {
+        for cos_theta in [1., 0.5, 0.1] {
+            let reflectance = thin_film_reflectance(cos_theta, 0., 1.33, 1.5);
+            assert_close(reflectance, fresnel_dielectric(cos_theta, 1.5));
+        }
+    }
+}
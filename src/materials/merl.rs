@@ -0,0 +1,365 @@
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord},
+        utils::Ray,
+    },
+    utils::{
+        frame::Frame,
+        vec4::{Color, Vec4},
+    },
+};
+use rand::prelude::*;
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    fs,
+};
+
+// table resolution of the MERL format
+const THETA_HALF_RESOLUTION: usize = 90;
+const THETA_DIFF_RESOLUTION: usize = 90;
+const PHI_DIFF_RESOLUTION: usize = 180; // only half of the circle is stored, thanks to reciprocity
+const DIMENSIONS: [usize; 3] = [
+    THETA_HALF_RESOLUTION,
+    THETA_DIFF_RESOLUTION,
+    PHI_DIFF_RESOLUTION,
+];
+const TABLE_SIZE: usize = THETA_HALF_RESOLUTION * THETA_DIFF_RESOLUTION * PHI_DIFF_RESOLUTION;
+
+// the stored values are scaled per channel
+const CHANNEL_SCALES: [f32; 3] = [1. / 1500., 1.15 / 1500., 1.66 / 1500.];
+
+const BLACK: Color = Color {
+    e: [0., 0., 0., 1.],
+};
+
+/**
+Measured isotropic BRDF in the MERL binary format, tabulated over the half / difference angles of
+Rusinkiewicz's parameterisation. Reflected directions are sampled half from the measured
+distribution of half vectors and half cosine weighted, so both shiny and diffuse measurements
+converge.
+*/
+pub struct Merl {
+    table: Vec<f32>, // brdf values, all the red values then green then blue
+    // sampling distribution over the theta half bins
+    theta_half_cdf: Vec<f32>,
+    theta_half_pmf: Vec<f32>,
+}
+
+impl Merl {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::decode(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// File contents: 3 little endian i32 dimensions followed by the table as f64 values
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 12 {
+            return Err("missing header".to_string());
+        }
+        let dimension =
+            |i: usize| i32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()) as usize;
+        let dimensions = [dimension(0), dimension(1), dimension(2)];
+        if dimensions != DIMENSIONS {
+            return Err(format!(
+                "unsupported table dimensions {:?}",
+                dimensions
+            ));
+        }
+
+        let data = &bytes[12..];
+        if data.len() != TABLE_SIZE * 3 * 8 {
+            return Err("table size does not match its dimensions".to_string());
+        }
+        let table = data
+            .chunks_exact(8)
+            .enumerate()
+            .map(|(i, value)| {
+                let value = f64::from_le_bytes(value.try_into().unwrap()) as f32;
+                // missing measurements are stored as negative values
+                value.max(0.) * CHANNEL_SCALES[i / TABLE_SIZE]
+            })
+            .collect();
+
+        Ok(Self::from_table(table))
+    }
+
+    fn from_table(table: Vec<f32>) -> Self {
+        // how much each theta half bin reflects, scaled by the solid angle it covers
+        let mut theta_half_pmf: Vec<f32> = (0..THETA_HALF_RESOLUTION)
+            .map(|bin| {
+                let start = bin * THETA_DIFF_RESOLUTION * PHI_DIFF_RESOLUTION;
+                let end = start + THETA_DIFF_RESOLUTION * PHI_DIFF_RESOLUTION;
+                let luminance: f32 = (0..3)
+                    .map(|channel| {
+                        table[channel * TABLE_SIZE + start..channel * TABLE_SIZE + end]
+                            .iter()
+                            .sum::<f32>()
+                    })
+                    .sum();
+                let (low, high) = (
+                    theta_half_at(bin as f32),
+                    theta_half_at(bin as f32 + 1.),
+                );
+                luminance * (low.cos() - high.cos()) + 1e-6
+            })
+            .collect();
+        let total: f32 = theta_half_pmf.iter().sum();
+        let mut cumulative = 0.;
+        let mut theta_half_cdf = vec![];
+        for probability in theta_half_pmf.iter_mut() {
+            *probability /= total;
+            cumulative += *probability;
+            theta_half_cdf.push(cumulative);
+        }
+
+        Self {
+            table,
+            theta_half_cdf,
+            theta_half_pmf,
+        }
+    }
+
+    /// BRDF for the local directions (normal is +z)
+    fn brdf(&self, wo: Vec4, wi: Vec4) -> Color {
+        let h = (wo + wi).normalise();
+        let theta_half = h.z().clamp(-1., 1.).acos();
+        let phi_half = h.y().atan2(h.x());
+
+        // incoming direction with the half vector rotated onto +z
+        let diff = rotate_y(rotate_z(wi, -phi_half), -theta_half);
+        let theta_diff = diff.z().clamp(-1., 1.).acos();
+        let mut phi_diff = diff.y().atan2(diff.x());
+        if phi_diff < 0. {
+            phi_diff += PI; // reciprocity
+        }
+
+        let theta_half_index = ((theta_half / FRAC_PI_2).max(0.).sqrt() *
+            THETA_HALF_RESOLUTION as f32)
+            .min(THETA_HALF_RESOLUTION as f32 - 1.) as usize;
+        let theta_diff_index = (theta_diff / FRAC_PI_2 * THETA_DIFF_RESOLUTION as f32)
+            .min(THETA_DIFF_RESOLUTION as f32 - 1.) as usize;
+        let phi_diff_index = (phi_diff / PI * PHI_DIFF_RESOLUTION as f32)
+            .min(PHI_DIFF_RESOLUTION as f32 - 1.) as usize;
+        let index = phi_diff_index +
+            theta_diff_index * PHI_DIFF_RESOLUTION +
+            theta_half_index * PHI_DIFF_RESOLUTION * THETA_DIFF_RESOLUTION;
+
+        Color::new(
+            self.table[index],
+            self.table[index + TABLE_SIZE],
+            self.table[index + TABLE_SIZE * 2],
+            1.,
+        )
+    }
+
+    /// Density of the half vector `h` under the tabulated distribution, per unit solid angle
+    fn half_vector_pdf(&self, h: Vec4) -> f32 {
+        let theta_half = h.z().clamp(-1., 1.).acos();
+        let u = (theta_half / FRAC_PI_2).sqrt();
+        let bin = ((u * THETA_HALF_RESOLUTION as f32) as usize).min(THETA_HALF_RESOLUTION - 1);
+        if u <= 0. || theta_half.sin() <= 0. {
+            return 0.;
+        }
+
+        // uniform in u within the bin, theta half = u² π / 2, uniform in phi half
+        let theta_half_pdf = self.theta_half_pmf[bin] * THETA_HALF_RESOLUTION as f32 / (u * PI);
+        theta_half_pdf / (2. * PI * theta_half.sin())
+    }
+
+    fn sample_half_vector(&self) -> Vec4 {
+        let mut rng = rand::thread_rng();
+        let target = rng.gen::<f32>();
+        let bin = self
+            .theta_half_cdf
+            .partition_point(|cumulative| *cumulative < target)
+            .min(THETA_HALF_RESOLUTION - 1);
+        let theta_half = theta_half_at(bin as f32 + rng.gen::<f32>());
+        let phi_half = 2. * PI * rng.gen::<f32>();
+
+        Vec4::new(
+            theta_half.sin() * phi_half.cos(),
+            theta_half.sin() * phi_half.sin(),
+            theta_half.cos(),
+            0.,
+        )
+    }
+
+    fn local_pdf(&self, wo: Vec4, wi: Vec4) -> f32 {
+        if wi.z() <= 0. {
+            return 0.;
+        }
+        let h = (wo + wi).normalise();
+        let half_vector = self.half_vector_pdf(h) / (4. * wo.dot(h).abs());
+        0.5 * half_vector + 0.5 * wi.z() / PI
+    }
+}
+
+/// Theta half at the start of the (fractional) bin, the bins are denser around the normal
+fn theta_half_at(bin: f32) -> f32 {
+    (bin / THETA_HALF_RESOLUTION as f32).powi(2) * FRAC_PI_2
+}
+
+fn rotate_z(v: Vec4, angle: f32) -> Vec4 {
+    let (sin, cos) = angle.sin_cos();
+    Vec4::new(
+        v.x() * cos - v.y() * sin,
+        v.x() * sin + v.y() * cos,
+        v.z(),
+        0.,
+    )
+}
+
+fn rotate_y(v: Vec4, angle: f32) -> Vec4 {
+    let (sin, cos) = angle.sin_cos();
+    Vec4::new(
+        v.x() * cos + v.z() * sin,
+        v.y(),
+        -v.x() * sin + v.z() * cos,
+        0.,
+    )
+}
+
+/// Shading frame on the side of the surface the ray comes from and the outgoing direction in it
+fn local_outgoing(ray: &Ray, hit_record: &HitRecord) -> (Frame, Vec4) {
+    let normal = if hit_record.geometric_normal.dot(ray.direction) > 0. {
+        -hit_record.normal
+    } else {
+        hit_record.normal
+    };
+    let frame = Frame::from_normal(normal);
+    (frame, frame.to_local(-ray.direction.normalise()))
+}
+
+impl Material for Merl {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (frame, wo) = local_outgoing(ray, hit_record);
+        let wi = if rand::thread_rng().gen::<f32>() < 0.5 {
+            let h = self.sample_half_vector();
+            -wo + h * (2. * wo.dot(h))
+        } else {
+            Vec4::random_cosine_direction()
+        };
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return None;
+        }
+
+        // weighted with the direction as `evaluate` and `pdf` get it back, the tabulated pdf jumps
+        // between bins so the local direction could land in a neighbouring one
+        let direction = frame.to_world(wi).normalise();
+        let wi = frame.to_local(direction.normalise());
+        let pdf = self.local_pdf(wo, wi);
+        if pdf <= 0. || wi.z() <= 0. {
+            return None;
+        }
+        let mut weight = self.brdf(wo, wi) * (wi.z() / pdf);
+        weight[3] = 1.;
+
+        Some((
+            weight,
+            Ray::new(hit_record.point_of_intersection, direction),
+        ))
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let (frame, wo) = local_outgoing(ray, hit_record);
+        let wi = frame.to_local(direction.normalise());
+        if wo.z() <= 0. || wi.z() <= 0. {
+            return BLACK;
+        }
+
+        let mut color = self.brdf(wo, wi) * wi.z();
+        color[3] = 1.;
+        color
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        let (frame, wo) = local_outgoing(ray, hit_record);
+        self.local_pdf(wo, frame.to_local(direction.normalise()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::vec4::Point;
+    use std::rc::Rc;
+
+    /// Table of a lambertian brdf, written out the way a MERL file stores it
+    fn lambertian_file(albedo: [f32; 3]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for dimension in [
+            THETA_HALF_RESOLUTION,
+            THETA_DIFF_RESOLUTION,
+            PHI_DIFF_RESOLUTION,
+        ] {
+            bytes.extend((dimension as i32).to_le_bytes());
+        }
+        for channel in 0..3 {
+            let value = (albedo[channel] / PI / CHANNEL_SCALES[channel]) as f64;
+            for _ in 0..TABLE_SIZE {
+                bytes.extend(value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn hit_record(material: Rc<dyn Material>) -> HitRecord {
+        HitRecord {
+            normal: Vec4::new(0., 1., 0., 0.),
+            geometric_normal: Vec4::new(0., 1., 0., 0.),
+            point_of_intersection: Point::new(0., 0., 0., 0.),
+            t: 1.,
+            u: 0.5,
+            v: 0.5,
+            dpdu: Vec4::new(1., 0., 0., 0.),
+            dpdv: Vec4::new(0., 0., 1., 0.),
+            material,
+            object_id: 0,
+            normal_map: None,
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_dimensions() {
+        let mut bytes = lambertian_file([1., 1., 1.]);
+        bytes[0] = 91;
+        assert!(Merl::decode(&bytes).is_err());
+        assert!(Merl::decode(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn lambertian_table_reflects_its_albedo() {
+        let albedo = [0.9, 0.5, 0.2];
+        let material: Rc<dyn Material> = Rc::new(Merl::decode(&lambertian_file(albedo)).unwrap());
+        let hit_record = hit_record(Rc::clone(&material));
+
+        for cos_theta in [1_f32, 0.5, 0.1] {
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+            let ray = Ray::new(
+                Point::new(-sin_theta, cos_theta, 0., 0.),
+                Vec4::new(sin_theta, -cos_theta, 0., 0.),
+            );
+
+            let sample_count = 20_000;
+            let mut sum = Color::new(0., 0., 0., 0.);
+            for _ in 0..sample_count {
+                if let Some((weight, new_ray)) = material.generate_reflected_ray(&ray, &hit_record)
+                {
+                    let expected = material.evaluate(&ray, &hit_record, new_ray.direction) /
+                        material.pdf(&ray, &hit_record, new_ray.direction);
+                    assert!((weight.x() - expected.x()).abs() < 1e-3 * expected.x().max(1.));
+                    sum += weight;
+                }
+            }
+            let average = sum / sample_count as f32;
+            for channel in 0..3 {
+                assert!(
+                    (average[channel] - albedo[channel]).abs() < 0.02,
+                    "albedo {:?}, expected {:?}",
+                    average.e,
+                    albedo
+                );
+            }
+        }
+    }
+}
@@ -1,18 +1,24 @@
 use crate::{
     ray_tracer::{
-        interface::{material_base::Material, object_base::HitRecord},
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
         utils::Ray,
     },
+    textures::solid_color::SolidColor,
     utils::vec4::{Color, Vec4},
 };
+use std::rc::Rc;
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Rc<dyn Texture>,
     fuzz: Option<f32>,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: Option<f32>) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>, fuzz: Option<f32>) -> Self {
         Self { albedo, fuzz }
     }
 }
@@ -43,7 +49,12 @@ impl Material for Metal {
         }
 
         Some((
-            self.albedo, // we are not using angle based attenuation here
+            // we are not using angle based attenuation here
+            self.albedo.value(
+                hit_record.u,
+                hit_record.v,
+                &hit_record.point_of_intersection,
+            ),
             new_ray,
         ))
     }
@@ -0,0 +1,310 @@
+use crate::utils::vec4::{Color, Vec4};
+use std::f32::consts::PI;
+
+// very low roughness makes the distribution a spike that float precision can't handle
+const MIN_ALPHA: f32 = 0.001;
+
+/// GGX / Trowbridge-Reitz microfacet distribution, in the local shading frame (normal is +z)
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    /// `roughness` is perceptual roughness in [0, 1] (alpha = roughness ^ 2)
+    pub fn new(roughness: f32) -> Self {
+        Self::anisotropic(roughness, roughness)
+    }
+
+    /// Separate roughness along the tangent (x) and the bitangent (y)
+    pub fn anisotropic(roughness_x: f32, roughness_y: f32) -> Self {
+        Self {
+            alpha_x: roughness_x.powi(2).max(MIN_ALPHA),
+            alpha_y: roughness_y.powi(2).max(MIN_ALPHA),
+        }
+    }
+
+    /// Density of microfacet normals `h`
+    pub fn d(&self, h: Vec4) -> f32 {
+        if h.z() <= 0. {
+            return 0.;
+        }
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let denominator = x * x + y * y + h.z() * h.z();
+        1. / (PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    fn lambda(&self, w: Vec4) -> f32 {
+        if w.z() == 0. {
+            return f32::INFINITY;
+        }
+        let tan_squared =
+            ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / (w.z() * w.z());
+        ((1. + tan_squared).sqrt() - 1.) / 2.
+    }
+
+    /// Masking, fraction of microfacets visible from `w`
+    pub fn g1(&self, w: Vec4) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// Height correlated masking and shadowing for a pair of directions
+    pub fn g2(&self, wo: Vec4, wi: Vec4) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of normals visible from `wo`, the pdf of `sample_visible_normal`
+    pub fn visible_normal_pdf(&self, wo: Vec4, h: Vec4) -> f32 {
+        if wo.z() <= 0. {
+            return 0.;
+        }
+        self.g1(wo) * wo.dot(h).max(0.) * self.d(h) / wo.z()
+    }
+
+    /// Density of reflecting into `wi` with a normal sampled by `sample_visible_normal` (per unit solid angle)
+    pub fn reflection_pdf(&self, wo: Vec4, wi: Vec4) -> f32 {
+        let h = wo + wi;
+        if h.is_degenerate() {
+            return 0.;
+        }
+        let h = h.normalise();
+        let cos_theta = wo.dot(h);
+        if cos_theta <= 0. {
+            return 0.;
+        }
+        self.visible_normal_pdf(wo, h) / (4. * cos_theta)
+    }
+
+    /**
+    Samples a microfacet normal visible from `wo` (Heitz 2018, "Sampling the GGX Distribution of Visible Normals").
+
+    - `wo`: outgoing direction in the local frame, on the +z side
+    - `u1`, `u2`: uniform random numbers in [0, 1)
+    */
+    pub fn sample_visible_normal(&self, wo: Vec4, u1: f32, u2: f32) -> Vec4 {
+        // stretch the view direction so the distribution becomes a hemisphere
+        let vh = Vec4::new(
+            self.alpha_x * wo.x(),
+            self.alpha_y * wo.y(),
+            wo.z(),
+            0.,
+        )
+        .normalise();
+
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0. {
+            Vec4::new(-vh.y(), vh.x(), 0., 0.) / length_squared.sqrt()
+        } else {
+            Vec4::new(1., 0., 0., 0.)
+        };
+        let t2 = vh.cross(t1);
+
+        // point on the projected half disk
+        let r = u1.sqrt();
+        let phi = 2. * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = t1 * p1 + t2 * p2 + vh * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+
+        // unstretch
+        Vec4::new(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            nh.z().max(1e-6),
+            0.,
+        )
+        .normalise()
+    }
+}
+
+/// Mirror reflection of `direction` about `normal`
+pub fn reflect(direction: Vec4, normal: Vec4) -> Vec4 {
+    direction - normal * direction.dot(normal) * 2.
+}
+
+/**
+Unpolarised Fresnel reflectance of a dielectric interface.
+
+- `cos_theta_i`: cosine of the incident angle, positive on the side the light comes from
+- `eta`: relative refractive index, transmitted side over incident side
+*/
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let cos_theta_i = cos_theta_i.clamp(0., 1.);
+    let sin_theta_t_squared = (1. - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin_theta_t_squared >= 1. {
+        return 1.; // TIR
+    }
+    let cos_theta_t = (1. - sin_theta_t_squared).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.
+}
+
+/// Unpolarised Fresnel reflectance of a conductor with complex refractive index `eta + i k`, per channel
+pub fn fresnel_conductor(cos_theta_i: f32, eta: Color, k: Color) -> Color {
+    let channel = |i: usize| {
+        fresnel_complex(
+            cos_theta_i.clamp(0., 1.),
+            Complex::new(eta[i], k[i]),
+        )
+    };
+    Color::new(channel(0), channel(1), channel(2), 1.)
+}
+
+fn fresnel_complex(cos_theta_i: f32, eta: Complex) -> f32 {
+    let sin_theta_i_squared = 1. - cos_theta_i * cos_theta_i;
+    let sin_theta_t_squared = Complex::new(sin_theta_i_squared, 0.) / (eta * eta);
+    let cos_theta_t = (Complex::new(1., 0.) - sin_theta_t_squared).sqrt();
+    let cos_theta_i = Complex::new(cos_theta_i, 0.);
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel.norm() + r_perpendicular.norm()) / 2.
+}
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    /// Squared magnitude
+    fn norm(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn sqrt(self) -> Self {
+        let magnitude = self.norm().sqrt();
+        if magnitude == 0. {
+            return Self::new(0., 0.);
+        }
+        let re = ((magnitude + self.re) / 2.).sqrt();
+        let im = ((magnitude - self.re) / 2.)
+            .max(0.)
+            .sqrt()
+            .copysign(self.im);
+        Self::new(re, im)
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let scale = 1. / rhs.norm();
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) * scale,
+            (self.im * rhs.re - self.re * rhs.im) * scale,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    const STEPS: usize = 512;
+
+    /// Midpoint rule over the hemisphere around +z
+    fn integrate(f: impl Fn(Vec4) -> f32) -> f32 {
+        let (d_theta, d_phi) = (PI / 2. / STEPS as f32, 2. * PI / STEPS as f32);
+        let mut sum = 0.;
+        for i in 0..STEPS {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..STEPS {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let w = Vec4::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    0.,
+                );
+                sum += f(w) * theta.sin();
+            }
+        }
+        sum * d_theta * d_phi
+    }
+
+    fn distributions() -> [TrowbridgeReitz; 3] {
+        [
+            TrowbridgeReitz::new(0.5),
+            TrowbridgeReitz::new(0.9),
+            TrowbridgeReitz::anisotropic(0.4, 0.7),
+        ]
+    }
+
+    /// Projected microfacet area adds up to the area of the surface
+    #[test]
+    fn distribution_is_normalized() {
+        for distribution in distributions() {
+            let area = integrate(|h| distribution.d(h) * h.z());
+            assert!((area - 1.).abs() < 0.01, "{} != 1", area);
+        }
+    }
+
+    #[test]
+    fn visible_normal_samples_match_pdf() {
+        let wo = Vec4::new(0.6, 0.2, 0.77, 0.).normalise();
+        let mut rng = rand::thread_rng();
+        for distribution in distributions() {
+            let total = integrate(|h| distribution.visible_normal_pdf(wo, h));
+            assert!((total - 1.).abs() < 0.01, "{} != 1", total);
+
+            // mean normal of the samples vs expected from the pdf
+            let sample_count = 200_000;
+            let mut mean = Vec4::new(0., 0., 0., 0.);
+            for _ in 0..sample_count {
+                mean += distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+            }
+            mean /= sample_count as f32;
+            for axis in 0..3 {
+                let expected = integrate(|h| h[axis] * distribution.visible_normal_pdf(wo, h));
+                assert!(
+                    (mean[axis] - expected).abs() < 0.01,
+                    "axis {}: {} != {}",
+                    axis,
+                    mean[axis],
+                    expected
+                );
+            }
+        }
+    }
+}
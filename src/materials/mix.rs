@@ -0,0 +1,275 @@
+use super::microfacet::fresnel_dielectric;
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        utils::Ray,
+    },
+    textures::solid_color::SolidColor,
+    utils::vec4::{Color, Vec4},
+};
+use rand::prelude::*;
+use std::rc::Rc;
+
+/// How much of the second material is used at a hit
+enum MixFactor {
+    /// Read from the red channel of the texture (eg: a dirt or rust mask)
+    Mask(Rc<dyn Texture>),
+    /// Fresnel reflectance of a dielectric with this refractive index, the second material shows at grazing angles
+    Fresnel(f32),
+}
+
+/**
+Blends two materials, eg: dirt over metal or a glossy layer over a diffuse one. At every hit one
+of the two materials is picked at random with the mix factor as the probability of picking the
+second one, which averages out to the blend of both. Emission, direct lighting and densities are
+blended with the mix factor directly, and so are cutouts.
+*/
+pub struct MixMaterial {
+    first: Rc<dyn Material>,
+    second: Rc<dyn Material>,
+    factor: MixFactor,
+}
+
+impl MixMaterial {
+    /// Constant blend, `factor` 0 is only the first material and 1 only the second
+    pub fn new(first: Rc<dyn Material>, second: Rc<dyn Material>, factor: f32) -> Self {
+        let factor = Color::new(factor, factor, factor, 1.);
+        Self::from_texture(first, second, Rc::new(SolidColor::new(factor)))
+    }
+
+    pub fn from_texture(
+        first: Rc<dyn Material>,
+        second: Rc<dyn Material>,
+        mask: Rc<dyn Texture>,
+    ) -> Self {
+        Self {
+            first,
+            second,
+            factor: MixFactor::Mask(mask),
+        }
+    }
+
+    pub fn fresnel(
+        first: Rc<dyn Material>,
+        second: Rc<dyn Material>,
+        refractive_index: f32,
+    ) -> Self {
+        Self {
+            first,
+            second,
+            factor: MixFactor::Fresnel(refractive_index),
+        }
+    }
+
+    fn factor(&self, ray: &Ray, hit_record: &HitRecord) -> f32 {
+        match &self.factor {
+            MixFactor::Mask(mask) => mask
+                .value(
+                    hit_record.u,
+                    hit_record.v,
+                    &hit_record.point_of_intersection,
+                )
+                .x(),
+            MixFactor::Fresnel(refractive_index) => {
+                let cos_theta = ray.direction.normalise().dot(hit_record.normal).abs();
+                fresnel_dielectric(cos_theta, *refractive_index)
+            }
+        }
+    }
+}
+
+impl Material for MixMaterial {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let picked = if rand::thread_rng().gen::<f32>() < self.factor(ray, hit_record) {
+            &self.second
+        } else {
+            &self.first
+        };
+
+        let (weight, mut new_ray) = picked.generate_reflected_ray(ray, hit_record)?;
+        // a discrete direction (eg: a mirror mixed with a diffuse) has no density even though the
+        // blended `pdf` would give it the density of the other material, lights are not weighed
+        // against it
+        if new_ray.scattering_pdf.is_none() && picked.pdf(ray, hit_record, new_ray.direction) <= 0.
+        {
+            new_ray.scattering_pdf = Some(0.);
+        }
+        Some((weight, new_ray))
+    }
+
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let factor = self.factor(ray, hit_record);
+        let mut color = self.first.evaluate(ray, hit_record, direction) * (1. - factor) +
+            self.second.evaluate(ray, hit_record, direction) * factor;
+        color[3] = 1.;
+        color
+    }
+
+    fn emitted(&self, ray: &Ray, hit_record: &HitRecord) -> Color {
+        let factor = self.factor(ray, hit_record);
+        let mut color = self.first.emitted(ray, hit_record) * (1. - factor) +
+            self.second.emitted(ray, hit_record) * factor;
+        color[3] = 1.;
+        color
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        let factor = self.factor(ray, hit_record);
+        self.first.pdf(ray, hit_record, direction) * (1. - factor) +
+            self.second.pdf(ray, hit_record, direction) * factor
+    }
+
+    fn opacity(&self, ray: &Ray, hit_record: &HitRecord) -> f32 {
+        let factor = self.factor(ray, hit_record);
+        self.first.opacity(ray, hit_record) * (1. - factor) +
+            self.second.opacity(ray, hit_record) * factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        materials::{emissive::Emissive, lambertian::Lambertian, metal::Metal},
+        utils::vec4::Point,
+    };
+
+    fn setup(material: &Rc<dyn Material>) -> (HitRecord, Ray) {
+        let hit_record = HitRecord {
+            normal: Vec4::new(0., 1., 0., 0.),
+            geometric_normal: Vec4::new(0., 1., 0., 0.),
+            point_of_intersection: Point::new(0., 0., 0., 0.),
+            t: 1.,
+            u: 0.5,
+            v: 0.5,
+            dpdu: Vec4::new(1., 0., 0., 0.),
+            dpdv: Vec4::new(0., 0., 1., 0.),
+            material: Rc::clone(material),
+            object_id: 0,
+            normal_map: None,
+        };
+        let ray = Ray::new(
+            Point::new(-1., 1., 0., 0.),
+            Vec4::new(1., -1., 0., 0.).normalise(),
+        );
+        (hit_record, ray)
+    }
+
+    fn lambertian(albedo: f32) -> Rc<dyn Material> {
+        Rc::new(Lambertian::new(Color::new(
+            albedo, albedo, albedo, 1.,
+        )))
+    }
+
+    fn directions() -> [Vec4; 3] {
+        [
+            Vec4::new(0., 1., 0., 0.),
+            Vec4::new(0.6, 0.3, -0.2, 0.).normalise(),
+            Vec4::new(0.2, -0.5, 0.1, 0.).normalise(),
+        ]
+    }
+
+    #[test]
+    fn half_mix_of_lambertians_is_the_average_lambertian() {
+        let mix: Rc<dyn Material> = Rc::new(MixMaterial::new(
+            lambertian(0.2),
+            lambertian(0.8),
+            0.5,
+        ));
+        let average = lambertian(0.5);
+        let (hit_record, ray) = setup(&mix);
+
+        for direction in directions() {
+            let (mixed, expected) = (
+                mix.evaluate(&ray, &hit_record, direction),
+                average.evaluate(&ray, &hit_record, direction),
+            );
+            for channel in 0..4 {
+                assert!((mixed[channel] - expected[channel]).abs() < 1e-6);
+            }
+            let (mixed, expected) = (
+                mix.pdf(&ray, &hit_record, direction),
+                average.pdf(&ray, &hit_record, direction),
+            );
+            assert!(
+                (mixed - expected).abs() < 1e-6,
+                "{} != {}",
+                mixed,
+                expected
+            );
+        }
+
+        // sampled weights average out to the averaged albedo
+        let sample_count = 20_000;
+        let mut sum = 0.;
+        for _ in 0..sample_count {
+            sum += mix.generate_reflected_ray(&ray, &hit_record).unwrap().0.x();
+        }
+        let albedo = sum / sample_count as f32;
+        assert!((albedo - 0.5).abs() < 0.02, "{} != 0.5", albedo);
+    }
+
+    #[test]
+    fn mask_of_zero_or_one_is_a_single_material() {
+        let first = lambertian(0.2);
+        let second: Rc<dyn Material> = Rc::new(Emissive::new(Color::new(1., 0.5, 0.25, 1.), 2.));
+
+        for (factor, expected) in [(0., &first), (1., &second)] {
+            let mask = Rc::new(SolidColor::new(Color::new(
+                factor, factor, factor, 1.,
+            )));
+            let mix: Rc<dyn Material> = Rc::new(MixMaterial::from_texture(
+                first.clone(),
+                second.clone(),
+                mask,
+            ));
+            let (hit_record, ray) = setup(&mix);
+
+            assert_eq!(
+                mix.emitted(&ray, &hit_record).e,
+                expected.emitted(&ray, &hit_record).e
+            );
+            for direction in directions() {
+                assert_eq!(
+                    mix.evaluate(&ray, &hit_record, direction).e,
+                    expected.evaluate(&ray, &hit_record, direction).e
+                );
+                assert_eq!(
+                    mix.pdf(&ray, &hit_record, direction),
+                    expected.pdf(&ray, &hit_record, direction)
+                );
+            }
+            for _ in 0..100 {
+                assert_eq!(
+                    mix.generate_reflected_ray(&ray, &hit_record)
+                        .map(|(weight, _)| weight.e),
+                    expected
+                        .generate_reflected_ray(&ray, &hit_record)
+                        .map(|(weight, _)| weight.e)
+                );
+            }
+        }
+    }
+
+    /// A mirror mixed with a diffuse is not weighed against lights with the density of the diffuse
+    #[test]
+    fn discrete_directions_have_no_density() {
+        let mirror: Rc<dyn Material> = Rc::new(Metal::new(Color::new(1., 1., 1., 1.), None));
+        let mix: Rc<dyn Material> = Rc::new(MixMaterial::new(lambertian(0.5), mirror, 0.5));
+        let (hit_record, ray) = setup(&mix);
+        let mirror_direction = Vec4::new(1., 1., 0., 0.).normalise();
+
+        let mut mirrored = 0;
+        for _ in 0..100 {
+            let (_, new_ray) = mix.generate_reflected_ray(&ray, &hit_record).unwrap();
+            if (new_ray.direction.normalise() - mirror_direction).length() < 1e-5 {
+                mirrored += 1;
+                assert!(mix.pdf(&ray, &hit_record, new_ray.direction) > 0.);
+                assert_eq!(new_ray.scattering_pdf, Some(0.));
+            } else {
+                assert_eq!(new_ray.scattering_pdf, None);
+            }
+        }
+        assert!(mirrored > 0);
+    }
+}
@@ -1,3 +1,15 @@
 pub mod lambertian;
 pub mod metal;
-pub mod dielectric;
\ No newline at end of file
+pub mod dielectric;
+pub mod conductor;
+pub mod microfacet;
+pub mod principled;
+pub mod rough_dielectric;
+pub mod thin_film;
+pub mod layered;
+pub mod mix;
+pub mod diffuse;
+pub mod subsurface;
+pub mod merl;
+pub mod emissive;
+pub mod cutout;
\ No newline at end of file
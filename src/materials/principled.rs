@@ -0,0 +1,594 @@
+use super::{
+    dielectric::Dielectric,
+    microfacet::{fresnel_dielectric, reflect, TrowbridgeReitz},
+};
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        utils::Ray,
+    },
+    textures::solid_color::SolidColor,
+    utils::{
+        frame::Frame,
+        vec4::{Color, Vec4},
+    },
+};
+use rand::prelude::*;
+use std::{f32::consts::PI, rc::Rc};
+
+const WHITE: Color = Color {
+    e: [1., 1., 1., 1.],
+};
+const CLEARCOAT_F0: f32 = 0.04; // polyurethane, ior 1.5
+
+fn lerp(a: Color, b: Color, t: f32) -> Color {
+    a * (1. - t) + b * t
+}
+
+/// Hue of a color with unit luminance, channels clamped to 1
+fn tint(color: Color) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0. {
+        return WHITE;
+    }
+    Color::new(
+        (color.x() / luminance).min(1.),
+        (color.y() / luminance).min(1.),
+        (color.z() / luminance).min(1.),
+        1.,
+    )
+}
+
+fn schlick(f0: Color, cos_theta: f32) -> Color {
+    let weight = (1. - cos_theta.clamp(0., 1.)).powi(5);
+    f0 * (1. - weight) + WHITE * weight
+}
+
+// Disney style principled material, a single material covering plastics, metals, glass and
+// coated surfaces. It is made of lobes:
+//
+//  - clearcoat: a separate, achromatic GGX layer on top of everything
+//  - specular: GGX reflection, tinted by the base color as the material becomes metallic
+//  - diffuse: Lambertian, blending towards the sheen color at grazing angles
+//  - transmission: GGX refraction through the surface
+//
+// Every lobe only gets the energy the lobes above it did not reflect, so the material never
+// reflects more light than it receives. A single lobe is picked per sample, proportional to its
+// expected contribution.
+pub struct Principled {
+    base_color: Rc<dyn Texture>,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,      // 0.5 gives the Fresnel of the ior
+    specular_tint: f32, // tints dielectric specular towards the base color
+    sheen: f32,
+    sheen_tint: f32, // tints sheen towards the base color
+    clearcoat: f32,
+    clearcoat_gloss: f32,
+    transmission: f32,
+    // against air, transmitted rays do not enter a nested medium (see Dielectric::with_priority)
+    refractive_index: f32,
+}
+
+impl Principled {
+    /// A rough, non metallic dielectric, other parameters are set with the `with_*` methods
+    pub fn new(base_color: Color) -> Self {
+        Self::from_texture(Rc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn from_texture(base_color: Rc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            clearcoat: 0.,
+            clearcoat_gloss: 1.,
+            transmission: 0.,
+            refractive_index: 1.5,
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> Self {
+        self.metallic = metallic.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_specular(mut self, specular: f32, specular_tint: f32) -> Self {
+        self.specular = specular.clamp(0., 1.);
+        self.specular_tint = specular_tint.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: f32, sheen_tint: f32) -> Self {
+        self.sheen = sheen.clamp(0., 1.);
+        self.sheen_tint = sheen_tint.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: f32, clearcoat_gloss: f32) -> Self {
+        self.clearcoat = clearcoat.clamp(0., 1.);
+        self.clearcoat_gloss = clearcoat_gloss.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: f32, refractive_index: f32) -> Self {
+        self.transmission = transmission.clamp(0., 1.);
+        self.refractive_index = refractive_index;
+        self
+    }
+
+    /// Fresnel of the dielectric base, scaled by the specular parameter (0.5 leaves it unchanged)
+    fn dielectric_fresnel(&self, cos_theta: f32, eta: f32) -> f32 {
+        let fresnel = fresnel_dielectric(cos_theta, eta);
+        if fresnel >= 1. {
+            1. // TIR
+        } else {
+            (fresnel * self.specular * 2.).min(1.)
+        }
+    }
+
+    /// Color of the specular reflection for a microfacet at angle `cos_theta` from the outgoing direction
+    fn specular_color(&self, base_color: Color, cos_theta: f32) -> Color {
+        let dielectric = lerp(WHITE, tint(base_color), self.specular_tint) *
+            self.dielectric_fresnel(cos_theta, self.refractive_index);
+        lerp(
+            dielectric,
+            schlick(base_color, cos_theta),
+            self.metallic,
+        )
+    }
+
+    /// GGX distribution of the clearcoat, the gloss maps to a fixed range of (low) roughnesses
+    fn clearcoat_distribution(&self) -> TrowbridgeReitz {
+        let alpha = 0.1 * (1. - self.clearcoat_gloss) + 0.001 * self.clearcoat_gloss;
+        TrowbridgeReitz::new(alpha.sqrt())
+    }
+
+    /// Shading frame on the side of the surface the ray comes from and whether that is the inside
+    fn local_frame(ray: &Ray, hit_record: &HitRecord) -> (Frame, bool) {
+        let inside = hit_record.geometric_normal.dot(ray.direction) > 0.;
+        let frame = Frame::from_normal(if inside {
+            -hit_record.normal
+        } else {
+            hit_record.normal
+        });
+        (frame, inside)
+    }
+
+    /**
+    Energy each lobe gets from outside of the object, evaluated at the macro surface normal.
+
+    Returns:
+
+    The clearcoat, specular, diffuse and transmission weights and the energy left under the coat
+    */
+    fn lobe_weights(&self, base_color: Color, cos_o: f32) -> ([f32; 4], f32) {
+        let coat = self.clearcoat * schlick(WHITE * CLEARCOAT_F0, cos_o).x();
+        let base = 1. - coat;
+        let dielectric =
+            (1. - self.metallic) * (1. - self.dielectric_fresnel(cos_o, self.refractive_index));
+
+        (
+            [
+                coat,
+                base * self.specular_color(base_color, cos_o).luminance(),
+                base * dielectric * (1. - self.transmission),
+                base * dielectric * self.transmission,
+            ],
+            base,
+        )
+    }
+
+    /// Color of the diffuse lobe, blending towards the sheen color at grazing angles
+    fn diffuse_color(&self, base_color: Color, wo: Vec4, wi: Vec4) -> Color {
+        let h = (wo + wi).normalise();
+        let sheen_color = lerp(WHITE, tint(base_color), self.sheen_tint);
+        let grazing = self.sheen * (1. - wi.dot(h).clamp(0., 1.)).powi(5);
+        lerp(base_color, sheen_color, grazing)
+    }
+
+    /// Reflection or refraction through the base from inside of the object, a plain rough dielectric
+    fn sample_inside(&self, wo: Vec4, distribution: &TrowbridgeReitz) -> Option<(Color, Vec4)> {
+        let eta = 1. / self.refractive_index;
+        let mut rng = rand::thread_rng();
+        let h = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+
+        let reflected = rng.gen::<f32>() < self.dielectric_fresnel(wo.dot(h), eta);
+        let wi = if reflected {
+            reflect(-wo, h)
+        } else {
+            Dielectric::refract(h, -wo, 1. / eta).normalise()
+        };
+        if reflected == (wi.z() <= 0.) {
+            return None; // scattered to the wrong side of the surface
+        }
+
+        let weight = distribution.g2(wo, wi) / distribution.g1(wo);
+        Some((WHITE * weight, wi))
+    }
+}
+
+impl Material for Principled {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let base_color = self.base_color.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+        let distribution = TrowbridgeReitz::new(self.roughness);
+
+        let (frame, inside) = Self::local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+
+        let (weight, wi) = if inside {
+            self.sample_inside(wo, &distribution)?
+        } else {
+            let (lobe_weights, base) = self.lobe_weights(base_color, wo.z());
+            let total: f32 = lobe_weights.iter().sum();
+            if total <= 0. {
+                return None;
+            }
+
+            // pick a lobe proportional to its weight
+            let mut rng = rand::thread_rng();
+            let mut target = rng.gen::<f32>() * total;
+            let mut lobe = lobe_weights.len() - 1;
+            for (i, lobe_weight) in lobe_weights.iter().enumerate() {
+                if target < *lobe_weight {
+                    lobe = i;
+                    break;
+                }
+                target -= lobe_weight;
+            }
+            let probability = lobe_weights[lobe] / total;
+
+            let (weight, wi) = match lobe {
+                // clearcoat
+                0 => {
+                    let distribution = self.clearcoat_distribution();
+                    let h = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+                    let wi = reflect(-wo, h);
+                    if wi.z() <= 0. {
+                        return None;
+                    }
+                    let fresnel = schlick(WHITE * CLEARCOAT_F0, wo.dot(h)).x();
+                    let weight =
+                        self.clearcoat * fresnel * distribution.g2(wo, wi) / distribution.g1(wo);
+                    (WHITE * weight, wi)
+                }
+                // specular
+                1 => {
+                    let h = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+                    let wi = reflect(-wo, h);
+                    if wi.z() <= 0. {
+                        return None;
+                    }
+                    let shadowing = distribution.g2(wo, wi) / distribution.g1(wo);
+                    (
+                        self.specular_color(base_color, wo.dot(h)) * (base * shadowing),
+                        wi,
+                    )
+                }
+                // diffuse, cosine weighted so the cosine and pdf cancel out
+                2 => {
+                    let wi = Vec4::random_cosine_direction();
+                    (
+                        self.diffuse_color(base_color, wo, wi) * lobe_weights[2],
+                        wi,
+                    )
+                }
+                // transmission
+                _ => {
+                    let h = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+                    let fresnel = self.dielectric_fresnel(wo.dot(h), self.refractive_index);
+                    let wi = Dielectric::refract(h, -wo, 1. / self.refractive_index).normalise();
+                    if wi.z() >= 0. {
+                        return None;
+                    }
+                    let shadowing = distribution.g2(wo, wi) / distribution.g1(wo);
+                    let weight = base *
+                        (1. - self.metallic) *
+                        self.transmission *
+                        (1. - fresnel) *
+                        shadowing;
+                    (base_color * weight, wi)
+                }
+            };
+
+            (weight / probability, wi)
+        };
+
+        let mut weight = weight;
+        weight[3] = 1.;
+        // not a nested medium, transmitted rays stay in the media around the object
+        Some((
+            weight,
+            Ray::new(
+                hit_record.point_of_intersection,
+                frame.to_world(wi).normalise(),
+            )
+            .with_media(ray.media.clone()),
+        ))
+    }
+
+    /// Only the reflection lobes, light from inside of the object or through it is not evaluated
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let mut color = Color::new(0., 0., 0., 1.);
+        let (frame, inside) = Self::local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+        let wi = frame.to_local(direction.normalise());
+        let h = wo + wi;
+        if inside || wo.z() <= 0. || wi.z() <= 0. || h.is_degenerate() {
+            return color;
+        }
+        let h = h.normalise();
+
+        let base_color = self.base_color.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+        let (lobe_weights, base) = self.lobe_weights(base_color, wo.z());
+
+        // microfacet lobes are F D G / (4 cos_o cos_i), times cos_i
+        let coat_distribution = self.clearcoat_distribution();
+        let coat = self.clearcoat *
+            schlick(WHITE * CLEARCOAT_F0, wo.dot(h)).x() *
+            coat_distribution.d(h) *
+            coat_distribution.g2(wo, wi) /
+            (4. * wo.z());
+        let distribution = TrowbridgeReitz::new(self.roughness);
+        let specular = self.specular_color(base_color, wo.dot(h)) *
+            (base * distribution.d(h) * distribution.g2(wo, wi) / (4. * wo.z()));
+        let diffuse = self.diffuse_color(base_color, wo, wi) * (lobe_weights[2] * wi.z() / PI);
+
+        color += WHITE * coat + specular + diffuse;
+        color[3] = 1.;
+        color
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        let (frame, inside) = Self::local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+        let wi = frame.to_local(direction.normalise());
+        if inside || wo.z() <= 0. || wi.z() <= 0. {
+            return 0.;
+        }
+
+        let base_color = self.base_color.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+        let (lobe_weights, _) = self.lobe_weights(base_color, wo.z());
+        let total: f32 = lobe_weights.iter().sum();
+        if total <= 0. {
+            return 0.;
+        }
+
+        // mixture of the lobes, each picked proportional to its weight
+        let pdf = lobe_weights[0] * self.clearcoat_distribution().reflection_pdf(wo, wi) +
+            lobe_weights[1] * TrowbridgeReitz::new(self.roughness).reflection_pdf(wo, wi) +
+            lobe_weights[2] * wi.z() / PI;
+        pdf / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_tracer::medium::{Medium, MediumStack};
+    use crate::utils::vec4::Point;
+
+    const SAMPLE_COUNT: u32 = 20_000;
+    const TOLERANCE: f32 = 0.02;
+
+    /// White furnace: average weight of the scattered rays for light coming in at `cos_theta`,
+    /// this is the fraction of energy the material reflects / transmits (per channel)
+    fn albedo(material: Principled, cos_theta: f32) -> Color {
+        let material: Rc<dyn Material> = Rc::new(material);
+        let (hit_record, ray) = setup(&material, cos_theta);
+
+        let mut sum = Color::new(0., 0., 0., 0.);
+        for _ in 0..SAMPLE_COUNT {
+            if let Some((weight, new_ray)) = material.generate_reflected_ray(&ray, &hit_record) {
+                assert!(weight.e.iter().all(|c| c.is_finite() && *c >= 0.));
+                assert!(!new_ray.direction.is_degenerate());
+                sum += weight;
+            }
+        }
+        sum / SAMPLE_COUNT as f32
+    }
+
+    /// Hit on an upwards facing surface and the ray arriving at it at `cos_theta`
+    fn setup(material: &Rc<dyn Material>, cos_theta: f32) -> (HitRecord, Ray) {
+        let hit_record = HitRecord {
+            normal: Vec4::new(0., 1., 0., 0.),
+            geometric_normal: Vec4::new(0., 1., 0., 0.),
+            point_of_intersection: Point::new(0., 0., 0., 0.),
+            t: 1.,
+            u: 0.5,
+            v: 0.5,
+            dpdu: Vec4::new(1., 0., 0., 0.),
+            dpdv: Vec4::new(0., 0., 1., 0.),
+            material: Rc::clone(material),
+            object_id: 0,
+            normal_map: None,
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let ray = Ray::new(
+            Point::new(-sin_theta, cos_theta, 0., 0.),
+            Vec4::new(sin_theta, -cos_theta, 0., 0.),
+        );
+        (hit_record, ray)
+    }
+
+    fn white() -> Principled {
+        Principled::new(Color::new(1., 1., 1., 1.))
+    }
+
+    fn assert_close(albedo: Color, expected: f32) {
+        for channel in 0..3 {
+            assert!(
+                (albedo[channel] - expected).abs() < TOLERANCE,
+                "albedo {:?}, expected {}",
+                albedo.e,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn white_diffuse_reflects_everything() {
+        for cos_theta in [1., 0.5, 0.1] {
+            assert_close(
+                albedo(white().with_specular(0., 0.), cos_theta),
+                1.,
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_white_metal_reflects_everything() {
+        for cos_theta in [1., 0.5, 0.1] {
+            assert_close(
+                albedo(
+                    white().with_metallic(1.).with_roughness(0.),
+                    cos_theta,
+                ),
+                1.,
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_clear_glass_loses_no_energy() {
+        for cos_theta in [1., 0.5, 0.1] {
+            let glass = white().with_roughness(0.).with_transmission(1., 1.5);
+            assert_close(albedo(glass, cos_theta), 1.);
+        }
+    }
+
+    #[test]
+    fn smooth_plastic_with_clearcoat_loses_no_energy() {
+        for cos_theta in [1., 0.5, 0.1] {
+            let plastic = white().with_roughness(0.).with_clearcoat(1., 1.);
+            assert_close(albedo(plastic, cos_theta), 1.);
+        }
+    }
+
+    #[test]
+    fn black_without_specular_absorbs_everything() {
+        let black = Principled::new(Color::new(0., 0., 0., 1.)).with_specular(0., 0.);
+        assert_close(albedo(black, 0.7), 0.);
+    }
+
+    #[test]
+    fn never_reflects_more_than_it_receives() {
+        for metallic in [0., 0.5, 1.] {
+            for roughness in [0., 0.3, 1.] {
+                for (specular, sheen, clearcoat, transmission) in [
+                    (0.5, 0., 0., 0.),
+                    (1., 1., 0., 0.),
+                    (0.5, 0., 1., 0.),
+                    (1., 0., 0.5, 1.),
+                    (1., 1., 1., 0.5),
+                ] {
+                    for cos_theta in [1., 0.5, 0.1] {
+                        let material = white()
+                            .with_metallic(metallic)
+                            .with_roughness(roughness)
+                            .with_specular(specular, 0.5)
+                            .with_sheen(sheen, 0.5)
+                            .with_clearcoat(clearcoat, 0.5)
+                            .with_transmission(transmission, 1.5);
+                        let albedo = albedo(material, cos_theta);
+                        assert!(
+                            albedo.e[..3].iter().all(|c| *c < 1. + TOLERANCE),
+                            "albedo {:?} for metallic {}, roughness {}, specular {}, sheen {}, clearcoat {}, transmission {}, cos_theta {}",
+                            albedo.e,
+                            metallic,
+                            roughness,
+                            specular,
+                            sheen,
+                            clearcoat,
+                            transmission,
+                            cos_theta
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reflecting the sampled directions with `evaluate` / `pdf` gives the same albedo as the sample weights
+    #[test]
+    fn evaluate_matches_sampling() {
+        for cos_theta in [1., 0.5, 0.1] {
+            let material: Rc<dyn Material> = Rc::new(
+                Principled::new(Color::new(0.8, 0.5, 0.2, 1.))
+                    .with_roughness(0.4)
+                    .with_sheen(0.5, 0.5)
+                    .with_clearcoat(1., 0.7),
+            );
+            let (hit_record, ray) = setup(&material, cos_theta);
+
+            let mut sampled = Color::new(0., 0., 0., 0.);
+            let mut evaluated = Color::new(0., 0., 0., 0.);
+            for _ in 0..SAMPLE_COUNT {
+                if let Some((weight, new_ray)) = material.generate_reflected_ray(&ray, &hit_record)
+                {
+                    sampled += weight;
+                    let pdf = material.pdf(&ray, &hit_record, new_ray.direction);
+                    if pdf > 0. {
+                        evaluated += material.evaluate(&ray, &hit_record, new_ray.direction) / pdf;
+                    }
+                }
+            }
+            let sampled = sampled / SAMPLE_COUNT as f32;
+            let evaluated = evaluated / SAMPLE_COUNT as f32;
+            for channel in 0..3 {
+                assert!(
+                    (sampled[channel] - evaluated[channel]).abs() < TOLERANCE,
+                    "sampled {:?}, evaluated {:?}",
+                    sampled.e,
+                    evaluated.e
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transmitted_rays_keep_the_surrounding_media() {
+        let material: Rc<dyn Material> = Rc::new(white().with_transmission(1., 1.5));
+        let (hit_record, ray) = setup(&material, 0.9);
+        let water = Medium {
+            id: usize::MAX,
+            refractive_index: 1.33,
+            priority: 0,
+            scattering: None,
+            absorption: Color::new(0., 0., 0., 0.),
+        };
+        let ray = ray.with_media(MediumStack::default().entered(water));
+
+        let mut transmitted = 0;
+        for _ in 0..1000 {
+            if let Some((_, new_ray)) = material.generate_reflected_ray(&ray, &hit_record) {
+                if new_ray.direction.y() < 0. {
+                    transmitted += 1;
+                    assert_eq!(new_ray.media.refractive_index(), 1.33);
+                }
+            }
+        }
+        assert!(transmitted > 0);
+    }
+}
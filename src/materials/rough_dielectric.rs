@@ -0,0 +1,245 @@
+use super::{
+    dielectric::Dielectric,
+    microfacet::{fresnel_dielectric, reflect, TrowbridgeReitz},
+};
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        utils::Ray,
+    },
+    textures::solid_color::SolidColor,
+    utils::{
+        frame::Frame,
+        vec4::{Color, Vec4},
+    },
+};
+use rand::prelude::*;
+use std::rc::Rc;
+
+// Frosted glass, a dielectric with a GGX microfacet distribution. Microfacet normals are drawn
+// from the distribution of visible normals, then reflection or refraction is picked with the
+// Fresnel term of that microfacet.
+pub struct RoughDielectric {
+    albedo: Rc<dyn Texture>,
+    // against air, unlike Dielectric::with_priority rough surfaces are not nested media
+    refractive_index: f32,
+    distribution: TrowbridgeReitz,
+}
+
+impl RoughDielectric {
+    pub fn new(albedo: Color, refractive_index: f32, roughness: f32) -> Self {
+        Self::from_texture(
+            Rc::new(SolidColor::new(albedo)),
+            refractive_index,
+            roughness,
+        )
+    }
+
+    pub fn from_texture(albedo: Rc<dyn Texture>, refractive_index: f32, roughness: f32) -> Self {
+        Self {
+            albedo,
+            refractive_index,
+            distribution: TrowbridgeReitz::new(roughness),
+        }
+    }
+
+    /// Refractive index of the side the ray goes into over the side it comes from, and the shading
+    /// frame on the side the ray comes from
+    fn local_frame(&self, ray: &Ray, hit_record: &HitRecord) -> (f32, Frame) {
+        if hit_record.geometric_normal.dot(ray.direction) < 0. {
+            // ray coming from outside of object
+            (
+                self.refractive_index,
+                Frame::from_normal(hit_record.normal),
+            )
+        } else {
+            (
+                1. / self.refractive_index,
+                Frame::from_normal(-hit_record.normal),
+            )
+        }
+    }
+
+    /// Reflected half vector and its Fresnel term, None if `wi` is not a reflection of `wo`
+    fn reflection(&self, eta: f32, wo: Vec4, wi: Vec4) -> Option<(Vec4, f32)> {
+        let h = wo + wi;
+        if wo.z() <= 0. || wi.z() <= 0. || h.is_degenerate() {
+            return None;
+        }
+        let h = h.normalise();
+        Some((h, fresnel_dielectric(wo.dot(h), eta)))
+    }
+}
+
+impl Material for RoughDielectric {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (eta, frame) = self.local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+
+        let mut rng = rand::thread_rng();
+        let h = self
+            .distribution
+            .sample_visible_normal(wo, rng.gen(), rng.gen());
+
+        // directions that end up on the wrong side of the surface are dropped, so the samples
+        // follow the same densities as `evaluate` and `pdf`
+        let wi = if rng.gen::<f32>() < fresnel_dielectric(wo.dot(h), eta) {
+            let wi = reflect(-wo, h);
+            if wi.z() <= 0. {
+                return None;
+            }
+            wi
+        } else {
+            let wi = Dielectric::refract(h, -wo, 1. / eta).normalise();
+            if wi.z() >= 0. {
+                return None;
+            }
+            wi
+        };
+
+        // Fresnel is accounted for by the choice between reflection and refraction
+        let shadowing = self.distribution.g2(wo, wi) / self.distribution.g1(wo);
+        let mut weight = self.albedo.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        ) * shadowing;
+        weight[3] = 1.;
+
+        // not a nested medium, transmitted rays stay in the media around the glass
+        Some((
+            weight,
+            Ray::new(
+                hit_record.point_of_intersection,
+                frame.to_world(wi).normalise(),
+            )
+            .with_media(ray.media.clone()),
+        ))
+    }
+
+    // only the reflection, shadow rays do not see lights through the glass so the refraction is
+    // left to sampling
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        let (eta, frame) = self.local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+        let wi = frame.to_local(direction.normalise());
+        let Some((h, fresnel)) = self.reflection(eta, wo, wi) else {
+            return Color::new(0., 0., 0., 1.);
+        };
+
+        // F D G / (4 cos_o cos_i), times cos_i
+        let albedo = self.albedo.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        );
+        let mut color = albedo *
+            (fresnel * self.distribution.d(h) * self.distribution.g2(wo, wi) / (4. * wo.z()));
+        color[3] = 1.;
+        color
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        let (eta, frame) = self.local_frame(ray, hit_record);
+        let wo = frame.to_local(-ray.direction.normalise());
+        let wi = frame.to_local(direction.normalise());
+        // reflection is picked with the Fresnel term of the sampled normal
+        self.reflection(eta, wo, wi).map_or(0., |(_, fresnel)| {
+            fresnel * self.distribution.reflection_pdf(wo, wi)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::sphere::Sphere;
+    use crate::ray_tracer::{
+        interface::object_base::Object,
+        medium::{Medium, MediumStack},
+    };
+    use crate::utils::vec4::Point;
+    use std::f32::consts::PI;
+
+    const SAMPLE_COUNT: u32 = 200_000;
+
+    /// Hit on the top of a glass sphere, and a ray arriving there at `cos_theta`
+    fn setup(cos_theta: f32) -> (HitRecord, Ray) {
+        let material: Rc<dyn Material> = Rc::new(RoughDielectric::new(
+            Color::new(1., 1., 1., 1.),
+            1.5,
+            0.6,
+        ));
+        let down = Ray::new(
+            Point::new(0., 2., 0., 0.),
+            Vec4::new(0., -1., 0., 0.),
+        );
+        let hit_record = Sphere::new(1., Point::new(0., 0., 0., 0.), material)
+            .is_ray_hit(&down, 0.001, f32::INFINITY)
+            .unwrap();
+
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let ray = Ray::new(
+            Point::new(-sin_theta, 1. + cos_theta, 0., 0.),
+            Vec4::new(sin_theta, -cos_theta, 0., 0.),
+        );
+        (hit_record, ray)
+    }
+
+    /// Reflections are sampled as often as `pdf` says, the integral of `pdf` over the hemisphere
+    #[test]
+    fn reflection_probability_matches_pdf() {
+        for cos_theta in [0.9, 0.3, 0.1] {
+            let (hit_record, ray) = setup(cos_theta);
+            let material = &hit_record.material;
+
+            let reflected = (0..SAMPLE_COUNT)
+                .filter(|_| {
+                    material
+                        .generate_reflected_ray(&ray, &hit_record)
+                        .is_some_and(|(_, new_ray)| new_ray.direction.y() > 0.)
+                })
+                .count() as f32 /
+                SAMPLE_COUNT as f32;
+
+            // uniformly sampled hemisphere, density 1 / 2π
+            let mut integral = 0.;
+            for _ in 0..SAMPLE_COUNT {
+                let mut direction = Vec4::random_in_unit_sphere().normalise();
+                direction[1] = direction.y().abs();
+                integral += material.pdf(&ray, &hit_record, direction) * 2. * PI;
+            }
+            let integral = integral / SAMPLE_COUNT as f32;
+
+            assert!(
+                (reflected - integral).abs() < 0.01,
+                "{}: reflected {}, integral {}",
+                cos_theta,
+                reflected,
+                integral
+            );
+        }
+    }
+
+    #[test]
+    fn transmitted_rays_keep_the_surrounding_media() {
+        let (hit_record, ray) = setup(0.9);
+        let water = Medium {
+            id: usize::MAX,
+            refractive_index: 1.33,
+            priority: 0,
+            scattering: None,
+            absorption: Color::new(0., 0., 0., 0.),
+        };
+        let ray = ray.with_media(MediumStack::default().entered(water));
+
+        for _ in 0..1000 {
+            if let Some((_, new_ray)) = hit_record
+                .material
+                .generate_reflected_ray(&ray, &hit_record)
+            {
+                assert_eq!(new_ray.media.refractive_index(), 1.33);
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use super::dielectric::Dielectric;
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord},
+        medium::Scattering,
+        utils::Ray,
+    },
+    utils::vec4::{Color, Vec4},
+};
+
+/**
+Subsurface scattering (eg: skin, wax, marble). Light refracts into the object, random walks through
+its inside and leaves it somewhere else, which softens the shading and lets light bleed through
+thin parts. The object has to be closed, the boundary is a smooth dielectric.
+
+- `albedo`: color the object has from far away, after all the scattering inside
+- `mean_free_path`: average distance light travels inside before it hits a particle, per channel
+*/
+pub struct Subsurface {
+    boundary: Dielectric,
+    scattering: Scattering,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Color, mean_free_path: Color, refractive_index: f32) -> Self {
+        let channel = |channel: usize| {
+            (
+                1. / mean_free_path[channel].max(1e-6),
+                single_scattering_albedo(albedo[channel]),
+            )
+        };
+        let (red, green, blue) = (channel(0), channel(1), channel(2));
+        let scattering = Scattering {
+            extinction: Color::new(red.0, green.0, blue.0, 1.),
+            albedo: Color::new(red.1, green.1, blue.1, 1.),
+            anisotropy: 0.,
+        };
+
+        Self {
+            boundary: Dielectric::new(Color::new(1., 1., 1., 1.), refractive_index)
+                .with_scattering(scattering),
+            scattering,
+        }
+    }
+
+    /// Henyey-Greenstein anisotropy of the particles inside, in (-1, 1). Skin is strongly forward scattering (~0.8).
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.scattering.anisotropy = anisotropy.clamp(-0.99, 0.99);
+        self.boundary = self.boundary.with_scattering(self.scattering);
+        self
+    }
+}
+
+/// Single scattering albedo giving the wanted multiple scattering `albedo` (Chiang et al.)
+fn single_scattering_albedo(albedo: f32) -> f32 {
+    let a = albedo.clamp(0., 1.);
+    let root = (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+    (1. - (4.09712 + 4.20863 * a - root).powi(2)).clamp(0., 1.)
+}
+
+impl Material for Subsurface {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        self.boundary.generate_reflected_ray(ray, hit_record)
+    }
+
+    // the boundary is smooth, so lights only reach the surface through the rays it scatters
+    fn evaluate(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> Color {
+        self.boundary.evaluate(ray, hit_record, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, hit_record: &HitRecord, direction: Vec4) -> f32 {
+        self.boundary.pdf(ray, hit_record, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    const SAMPLE_COUNT: u32 = 50_000;
+
+    /// Share of the diffuse light entering a half space (z < 0) that random walks back out
+    fn escaping_share(scattering: &Scattering) -> f32 {
+        let mut rng = rand::thread_rng();
+        let mut escaped = 0;
+        for _ in 0..SAMPLE_COUNT {
+            let mut depth = 0.;
+            // cosine distributed, like light coming from the whole sky
+            let cos_theta = rng.gen::<f32>().sqrt();
+            let mut direction = Vec4::new(
+                (1. - cos_theta * cos_theta).sqrt(),
+                0.,
+                -cos_theta,
+                0.,
+            );
+            loop {
+                depth += direction.z() * -(1. - rng.gen::<f32>()).ln() / scattering.extinction.x();
+                if depth > 0. {
+                    escaped += 1;
+                    break;
+                }
+                if rng.gen::<f32>() >= scattering.albedo.x() {
+                    break;
+                }
+                direction = scattering.sample_direction(direction);
+            }
+        }
+        escaped as f32 / SAMPLE_COUNT as f32
+    }
+
+    /// Furnace like check of the albedo inversion: under uniform lighting, an index matched half
+    /// space with the wanted albedo sends back that much of the light shining onto it
+    #[test]
+    fn random_walks_give_back_the_wanted_albedo() {
+        for albedo in [0.2, 0.5, 0.8] {
+            let material = Subsurface::new(
+                Color::new(albedo, albedo, albedo, 1.),
+                Color::new(1., 1., 1., 1.),
+                1.,
+            );
+            let escaped = escaping_share(&material.scattering);
+            assert!(
+                (escaped - albedo).abs() < 0.02,
+                "albedo {albedo}: {escaped} escaped"
+            );
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use super::microfacet::reflect;
+use crate::{
+    ray_tracer::{
+        interface::{material_base::Material, object_base::HitRecord, texture_base::Texture},
+        spectrum::reflectance_to_rgb,
+        utils::Ray,
+    },
+    textures::solid_color::SolidColor,
+    utils::vec4::Color,
+};
+use rand::prelude::*;
+use std::{f32::consts::PI, rc::Rc};
+
+// wavelengths the interference pattern is integrated over, enough for films up to ~1µm thick
+const SPECTRAL_SAMPLES: usize = 16;
+
+/**
+Reflectance of a thin film sitting on a substrate, seen from air (Airy summation of the light
+bouncing inside the film, averaged over both polarisations). Light reflected off the top and the
+bottom of the film interferes, which makes the reflection iridescent.
+
+- `thickness`: thickness of the film in nm
+- `substrate_refractive_index`: 1 for a free standing film (eg: soap bubble)
+*/
+pub fn thin_film_reflectance(
+    cos_theta_i: f32,
+    thickness: f32,
+    film_refractive_index: f32,
+    substrate_refractive_index: f32,
+) -> Color {
+    reflectance_to_rgb(
+        airy_reflectance(
+            cos_theta_i,
+            thickness,
+            film_refractive_index,
+            substrate_refractive_index,
+        ),
+        SPECTRAL_SAMPLES,
+    )
+}
+
+/// Reflectance of the film as a function of the wavelength in nm, see [`thin_film_reflectance`]
+fn airy_reflectance(
+    cos_theta_i: f32,
+    thickness: f32,
+    film_refractive_index: f32,
+    substrate_refractive_index: f32,
+) -> impl Fn(f32) -> f32 {
+    let cos_theta_i = cos_theta_i.clamp(0., 1.);
+    let sin_theta_i_squared = 1. - cos_theta_i * cos_theta_i;
+
+    let cos_theta_t = |refractive_index: f32| {
+        let sin_theta_t_squared = sin_theta_i_squared / (refractive_index * refractive_index);
+        (sin_theta_t_squared < 1.).then(|| (1. - sin_theta_t_squared).sqrt())
+    };
+    let interfaces =
+        cos_theta_t(film_refractive_index).zip(cos_theta_t(substrate_refractive_index));
+
+    let (n1, n2, n3) = (
+        1.,
+        film_refractive_index,
+        substrate_refractive_index,
+    );
+    move |wavelength| {
+        let Some((cos_film, cos_substrate)) = interfaces else {
+            return 1.; // TIR
+        };
+
+        let r12_perpendicular =
+            (n1 * cos_theta_i - n2 * cos_film) / (n1 * cos_theta_i + n2 * cos_film);
+        let r23_perpendicular =
+            (n2 * cos_film - n3 * cos_substrate) / (n2 * cos_film + n3 * cos_substrate);
+        let r12_parallel = (n2 * cos_theta_i - n1 * cos_film) / (n2 * cos_theta_i + n1 * cos_film);
+        let r23_parallel =
+            (n3 * cos_film - n2 * cos_substrate) / (n3 * cos_film + n2 * cos_substrate);
+
+        // phase difference between the light reflected off the top and the bottom of the film
+        let phase = 4. * PI * n2 * thickness * cos_film / wavelength;
+        let airy = |r12: f32, r23: f32| {
+            let interference = 2. * r12 * r23 * phase.cos();
+            (r12 * r12 + r23 * r23 + interference) / (1. + r12 * r12 * r23 * r23 + interference)
+        };
+
+        (airy(r12_perpendicular, r23_perpendicular) + airy(r12_parallel, r23_parallel)) / 2.
+    }
+}
+
+/// Free standing thin film, eg: a soap bubble. Light is either reflected or passes straight through.
+/// Both are discrete directions, lights only reach the film through the rays it scatters.
+pub struct ThinFilm {
+    thickness: Rc<dyn Texture>, // in nm, read from the red channel
+    refractive_index: f32,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, refractive_index: f32) -> Self {
+        Self::from_texture(
+            Rc::new(SolidColor::new(Color::new(
+                thickness, thickness, thickness, 1.,
+            ))),
+            refractive_index,
+        )
+    }
+
+    /// Film with varying thickness (eg: a draining bubble), the thickness in nm is read from the red channel
+    pub fn from_texture(thickness: Rc<dyn Texture>, refractive_index: f32) -> Self {
+        Self {
+            thickness,
+            refractive_index,
+        }
+    }
+}
+
+impl Material for ThinFilm {
+    fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let direction = ray.direction.normalise();
+        let thickness = self
+            .thickness
+            .value(
+                hit_record.u,
+                hit_record.v,
+                &hit_record.point_of_intersection,
+            )
+            .x();
+
+        // the film looks the same from both sides
+        let cos_theta = direction.dot(hit_record.normal).abs();
+        // the interference depends on the wavelength, a spectral path only carries the hero one on
+        let (reflectance, wavelengths) = match ray.wavelengths {
+            Some(wavelengths) => {
+                let spectrum = airy_reflectance(cos_theta, thickness, self.refractive_index, 1.);
+                let reflectance = spectrum(wavelengths.hero());
+                (
+                    Color::new(reflectance, reflectance, reflectance, 1.),
+                    Some(wavelengths.collapse()),
+                )
+            }
+            None => (
+                thin_film_reflectance(cos_theta, thickness, self.refractive_index, 1.),
+                None,
+            ),
+        };
+        let transmittance = Color::new(
+            1. - reflectance.x(),
+            1. - reflectance.y(),
+            1. - reflectance.z(),
+            1.,
+        );
+
+        // pick reflection or transmission proportional to their average energy
+        let reflect_probability =
+            ((reflectance.x() + reflectance.y() + reflectance.z()) / 3.).clamp(0.001, 0.999);
+        if rand::thread_rng().gen::<f32>() < reflect_probability {
+            let mut weight = reflectance / reflect_probability;
+            weight[3] = 1.;
+            return Some((
+                weight,
+                Ray::new(
+                    hit_record.point_of_intersection,
+                    reflect(direction, hit_record.normal),
+                )
+                .with_wavelengths(wavelengths),
+            ));
+        }
+
+        let mut weight = transmittance / (1. - reflect_probability);
+        weight[3] = 1.;
+        Some((
+            weight,
+            Ray::new(hit_record.point_of_intersection, direction).with_wavelengths(wavelengths),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::sphere::Sphere;
+    use crate::ray_tracer::{
+        interface::object_base::Object,
+        spectrum::{Wavelengths, LAMBDA_MAX, LAMBDA_MIN},
+    };
+    use crate::utils::vec4::{Point, Vec4};
+
+    /// Soap bubbles reflect nothing where twice the optical thickness is a whole wavelength
+    #[test]
+    fn spectral_rays_reflect_at_their_hero_wavelength() {
+        let refractive_index = 1.33;
+        let hero = 550.;
+        let film = ThinFilm::new(hero / (2. * refractive_index), refractive_index);
+        let sphere = Sphere::new(1., Point::new(0., 0., 0., 0.), Rc::new(film));
+        let ray = Ray::new(
+            Point::new(0., 2., 0., 0.),
+            Vec4::new(0., -1., 0., 0.),
+        );
+        let hit_record = sphere.is_ray_hit(&ray, 0.001, f32::INFINITY).unwrap();
+
+        // the other wavelengths still reflect
+        let rgb_reflectance = thin_film_reflectance(
+            1.,
+            hero / (2. * refractive_index),
+            refractive_index,
+            1.,
+        );
+        assert!(rgb_reflectance.x() > 0.01);
+
+        let wavelengths = Wavelengths::sample((hero - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN));
+        assert!((wavelengths.hero() - hero).abs() < 1e-3);
+        let ray = ray.with_wavelengths(Some(wavelengths));
+        for _ in 0..1000 {
+            let (weight, new_ray) = hit_record
+                .material
+                .generate_reflected_ray(&ray, &hit_record)
+                .unwrap();
+            assert!(new_ray.wavelengths.unwrap().is_collapsed());
+            if new_ray.direction.y() > 0. {
+                assert!(weight.x() < 1e-3, "reflected {}", weight.x());
+            } else {
+                assert!(
+                    (weight.x() - 1.).abs() < 1e-2,
+                    "transmitted {}",
+                    weight.x()
+                );
+            }
+        }
+    }
+}
@@ -1,17 +1,23 @@
+use crate::materials::cutout::Cutout;
 use crate::ray_tracer::{
     interface::{
         material_base::Material,
-        object_base::{HitRecord, Object},
+        normal_map_base::NormalMap,
+        object_base::{next_object_id, HitRecord, Object},
+        texture_base::Texture,
     },
     utils::Ray,
 };
-use crate::utils::vec4::Point;
-use std::rc::Rc;
+use crate::utils::vec4::{Point, Vec4};
+use std::{f32::consts::PI, rc::Rc};
 
+#[derive(Clone)]
 pub struct Sphere {
     radius: f32,
     center: Point,
     material: Rc<dyn Material>, // TODO: Why can't this not be done using just reference or Box
+    id: usize,
+    normal_map: Option<Rc<dyn NormalMap>>,
 }
 
 impl Sphere {
@@ -20,8 +26,70 @@ impl Sphere {
             radius,
             center,
             material,
+            id: next_object_id(),
+            normal_map: None,
         }
     }
+
+    pub fn with_normal_map(mut self, normal_map: Rc<dyn NormalMap>) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    /// Cuts holes into the surface where the red channel of `opacity` is below 1, see [`Cutout`]
+    pub fn with_opacity(mut self, opacity: Rc<dyn Texture>) -> Self {
+        self.material = Rc::new(Cutout::new(self.material, opacity));
+        self
+    }
+
+    pub fn center(&self) -> Point {
+        self.center
+    }
+
+    /// Negative for spheres turned inside out
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// See [`HitRecord::object_id`], clones keep the id of the sphere they were cloned from
+    pub fn object_id(&self) -> usize {
+        self.id
+    }
+
+    /**
+    Spherical co-ordinates of a point on the unit sphere.
+
+    - `u`: longitude, 0 at -x going around through +z, +x, -z
+    - `v`: latitude, 0 at the bottom (-y), 1 at the top (+y)
+    */
+    fn uv(point: Point) -> (f32, f32) {
+        let theta = (-point.y()).clamp(-1., 1.).acos();
+        let phi = (-point.z()).atan2(point.x()) + PI;
+
+        (phi / (2. * PI), theta / PI)
+    }
+
+    /// Partial derivatives of the point along u and v, for a point on the unit sphere
+    fn tangents(&self, point: Point) -> (Vec4, Vec4) {
+        let radius = self.radius.abs();
+        let theta = (-point.y()).clamp(-1., 1.).acos();
+        let phi = (-point.z()).atan2(point.x()) + PI;
+
+        let dpdu = Vec4::new(
+            theta.sin() * phi.sin(),
+            0.,
+            theta.sin() * phi.cos(),
+            0.,
+        ) * (2. * PI * radius);
+        let dpdv = Vec4::new(
+            -theta.cos() * phi.cos(),
+            theta.sin(),
+            theta.cos() * phi.sin(),
+            0.,
+        ) * (PI * radius);
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Object for Sphere {
@@ -53,11 +121,22 @@ impl Object for Sphere {
                 (point_of_intersection - self.center).normalise()
             };
 
+            let local_point = (point_of_intersection - self.center).normalise();
+            let (u, v) = Self::uv(local_point);
+            let (dpdu, dpdv) = self.tangents(local_point);
+
             Some(HitRecord {
                 point_of_intersection,
                 normal,
+                geometric_normal: normal,
                 t,
+                u,
+                v,
+                dpdu,
+                dpdv,
                 material: Rc::clone(&self.material),
+                object_id: self.id,
+                normal_map: self.normal_map.clone(),
             })
         }
     }
@@ -1,27 +1,40 @@
 use super::{
     interface::camera_base::Camera,
     interface::object_base::HitRecord,
-    utils::{map_to_range, Ray},
+    light_sampler::{LightSampler, LightSelection},
+    medium::Scattering,
+    spectrum::Wavelengths,
+    utils::Ray,
 };
 use crate::scene::Scene;
-use crate::utils::vec4::Color;
+use crate::utils::vec4::{Color, Point, Vec4};
 use indicatif::ProgressBar;
 use rand::prelude::*;
 
 const WHITE: Color = Color {
     e: [1., 1., 1., 1.],
 };
-const BLUE: Color = Color {
-    e: [130. / 255., 170. / 255., 227. / 255., 1.],
-};
 const BLACK: Color = Color {
     e: [0. / 255., 0. / 255., 0. / 255., 1.],
 };
+const TRANSPARENT: Color = Color {
+    e: [0., 0., 0., 0.],
+};
 const MAX_REFLECTION_DEPTH: u8 = 5;
+const MAX_SCATTERING_EVENTS: u32 = 256; // per walk through a participating medium, not counted as bounces
 
 const T_MIN: f32 = 0.0001; // not 0 to avoid shadow acne
 const T_MAX: f32 = f32::INFINITY;
 
+/// MIS weight of a sample drawn with density `pdf`, against another strategy with density `other_pdf`
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (pdf, other_pdf) = (pdf * pdf, other_pdf * other_pdf);
+    if pdf + other_pdf <= 0. {
+        return 0.;
+    }
+    pdf / (pdf + other_pdf)
+}
+
 pub struct Engine {
     camera: Box<dyn Camera>,
     scene: Scene,
@@ -31,6 +44,12 @@ pub struct Engine {
     // anti-aliasing
     anti_aliasing: bool,
     anti_aliasing_sample_count: u32,
+
+    // paths carry sampled wavelengths instead of RGB
+    spectral: bool,
+
+    // picks the lights for direct lighting
+    light_sampler: LightSampler,
 }
 
 impl Engine {
@@ -43,20 +62,88 @@ impl Engine {
         anti_aliasing_sample_count: u32,
     ) -> Self {
         Self {
+            light_sampler: LightSampler::new(LightSelection::All, &scene.lights),
             camera,
             scene,
             image_height,
             image_width,
             anti_aliasing,
             anti_aliasing_sample_count,
+            spectral: false,
         }
     }
 
+    /**
+    Renders spectrally (hero wavelength sampling): every camera sample traces a few wavelengths,
+    RGB albedos and lights are upsampled to spectra along the path and the result is converted back
+    to RGB at the film. Slower and noisier in color, but needed for wavelength dependent effects
+    (dispersion).
+    */
+    pub fn with_spectral_rendering(mut self) -> Self {
+        self.spectral = true;
+        self
+    }
+
+    /**
+    How the scene's lights are picked for direct lighting, every light at every hit by default.
+    Scenes with many lights render faster picking a single one per hit.
+    */
+    pub fn with_light_selection(mut self, selection: LightSelection) -> Self {
+        self.light_sampler = LightSampler::new(selection, &self.scene.lights);
+        self
+    }
+
+    /// How many samples each of the scene's lights gave for direct lighting, for debugging
+    pub fn light_sample_counts(&self) -> Vec<u64> {
+        self.light_sampler.counts()
+    }
+
     pub fn ray_color(&self, ray: &Ray, depth: u8) -> Color {
         if depth >= MAX_REFLECTION_DEPTH {
             return BLACK;
         }
 
+        let closest_hit_record = self.closest_hit(ray);
+        let Some(medium) = ray.media.current().copied() else {
+            return self.shade(ray, closest_hit_record, depth);
+        };
+
+        // inside of a scattering volume the path walks through it before reaching a surface
+        if let Some(scattering) = medium.scattering {
+            return self.trace_volume(
+                ray,
+                closest_hit_record,
+                scattering,
+                medium.absorption,
+                depth,
+            );
+        }
+
+        // the medium fills the whole segment up to the next surface, whatever that surface is
+        let t = closest_hit_record
+            .as_ref()
+            .map_or(T_MAX, |hit_record| hit_record.t);
+        self.shade(ray, closest_hit_record, depth) * Self::transmittance(ray, medium.absorption, t)
+    }
+
+    /// Fraction of the light that is not absorbed along the ray up to `t` (Beer-Lambert)
+    fn transmittance(ray: &Ray, absorption: Color, t: f32) -> Color {
+        let absorption = match ray.wavelengths {
+            Some(wavelengths) => wavelengths.upsample(absorption),
+            None => absorption,
+        };
+        // t is in units of the ray direction, the absorption is per unit distance
+        let distance = t * ray.direction.length();
+        let mut transmittance = WHITE;
+        for channel in 0..3 {
+            if absorption[channel] > 0. {
+                transmittance[channel] = (-absorption[channel] * distance).exp();
+            }
+        }
+        transmittance
+    }
+
+    fn closest_hit(&self, ray: &Ray) -> Option<HitRecord> {
         // keeps track of closest hit t value for the ray
         let mut closest_hit_record: Option<HitRecord> = None;
 
@@ -76,8 +163,103 @@ impl Engine {
             };
         }
 
+        closest_hit_record
+    }
+
+    /**
+    Random walk through a participating medium (eg: subsurface scattering). The distance to the
+    next interaction is sampled from the extinction of a random channel and weighted against all
+    of them (spectral MIS), so chromatic media do not get noisy. The walk ends when the path
+    reaches the surface bounding the medium, which is then shaded as usual. Absorption of the medium
+    on top of its scattering dims every step of the walk.
+    */
+    fn trace_volume(
+        &self,
+        ray: &Ray,
+        closest_hit_record: Option<HitRecord>,
+        scattering: Scattering,
+        absorption: Color,
+        depth: u8,
+    ) -> Color {
+        let mut rng = rand::thread_rng();
+        let (extinction, albedo) = match ray.wavelengths {
+            Some(wavelengths) => (
+                wavelengths.upsample(scattering.extinction),
+                wavelengths.upsample(scattering.albedo),
+            ),
+            None => (scattering.extinction, scattering.albedo),
+        };
+
+        let mut ray = ray.clone();
+        let mut closest_hit_record = closest_hit_record;
+        let mut throughput = WHITE;
+        for _ in 0..MAX_SCATTERING_EVENTS {
+            // t is in units of the ray direction, the extinction is per unit distance
+            let speed = ray.direction.length();
+            let t_max = closest_hit_record
+                .as_ref()
+                .map_or(T_MAX, |hit_record| hit_record.t);
+            let channel = rng.gen_range(0..3);
+            // channels without extinction never interact, their light goes straight through
+            let t = match extinction[channel] * speed {
+                rate if rate > 0. => -(1. - rng.gen::<f32>()).ln() / rate,
+                _ => T_MAX,
+            };
+
+            if t >= t_max {
+                // reached the boundary, weighted by the chance of getting this far
+                let transmittance = |channel: usize| match extinction[channel] {
+                    extinction if extinction > 0. => (-extinction * t_max * speed).exp(),
+                    _ => 1.,
+                };
+                let probability = (0..3).map(transmittance).sum::<f32>() / 3.;
+                for channel in 0..3 {
+                    throughput[channel] *= transmittance(channel) / probability;
+                }
+                throughput *= Self::transmittance(&ray, absorption, t_max);
+                return self.shade(&ray, closest_hit_record, depth) * throughput;
+            }
+
+            // scattered, weighted by the density of interacting here
+            let density =
+                |channel: usize| extinction[channel] * (-extinction[channel] * t * speed).exp();
+            let probability = (0..3).map(density).sum::<f32>() / 3.;
+            for channel in 0..3 {
+                throughput[channel] *= albedo[channel] * density(channel) / probability;
+            }
+            throughput *= Self::transmittance(&ray, absorption, t);
+
+            ray = Ray::new(
+                ray.at(t),
+                scattering.sample_direction(ray.direction.normalise()),
+            )
+            .with_media(ray.media.clone())
+            .with_wavelengths(ray.wavelengths);
+            closest_hit_record = self.closest_hit(&ray);
+        }
+
+        BLACK // walked too long, treat the light as absorbed
+    }
+
+    /// Light leaving the closest hit back along the ray, or the background if nothing was hit
+    fn shade(&self, ray: &Ray, closest_hit_record: Option<HitRecord>, depth: u8) -> Color {
         // if ray has hit at least one object
-        if let Some(hit_record) = closest_hit_record {
+        if let Some(mut hit_record) = closest_hit_record {
+            // cutouts let the ray carry on through the surface, this does not count as a bounce
+            if !Self::is_opaque(ray, &hit_record) {
+                let continued_ray = Ray::new(hit_record.point_of_intersection, ray.direction)
+                    .with_media(ray.media.clone())
+                    .with_wavelengths(ray.wavelengths)
+                    .with_scattering_pdf(ray.scattering_pdf);
+                return self.ray_color(&continued_ray, depth);
+            }
+
+            // shading normal from normal / bump maps, only computed for the closest hit. Which side
+            // of the surface the ray is on is still decided by the geometric normal
+            if let Some(normal_map) = hit_record.normal_map.clone() {
+                hit_record.normal = normal_map.perturb(&hit_record);
+            }
+
             // INFO Normal debugger code
             // If this is on, light should not reflect in this mode
             // let r = map_to_range(hit_record.normal.x(), -1., 1., 0., 1.);
@@ -85,32 +267,227 @@ impl Engine {
             // let b = map_to_range(hit_record.normal.z(), -1., 1., 0., 1.);
             // return Color::new(r, g, b, 1.);
 
+            let mut emitted = hit_record.material.emitted(ray, &hit_record);
+            if emitted.x() > 0. || emitted.y() > 0. || emitted.z() > 0. {
+                emitted *= self.emission_weight(ray, &hit_record);
+            }
+            let direct = self.direct_light(ray, &hit_record);
+            for channel in 0..3 {
+                emitted[channel] += direct[channel];
+            }
+
             // reflect and attenuate
-            if let Some((attenuated_color, new_ray)) =
+            if let Some((attenuated_color, mut new_ray)) =
                 hit_record.material.generate_reflected_ray(ray, &hit_record)
             {
-                return self.ray_color(&new_ray, depth + 1) * attenuated_color;
+                // reflected rays stay in the same media, transmissive materials update them
+                let normal = hit_record.geometric_normal;
+                if new_ray.direction.dot(normal) * ray.direction.dot(normal) < 0. {
+                    new_ray.media = ray.media.clone();
+                }
+                // discrete directions (mirrors, glass) have no density to weigh lights against,
+                // materials that know better than `pdf` (eg: mixes) set the density themselves
+                let pdf = new_ray.scattering_pdf.unwrap_or_else(|| {
+                    hit_record.material.pdf(ray, &hit_record, new_ray.direction)
+                });
+                new_ray.scattering_pdf = (pdf > 0.).then_some(pdf);
+
+                let mut attenuated_color = match ray.wavelengths {
+                    Some(wavelengths) => {
+                        let next = *new_ray.wavelengths.get_or_insert(wavelengths);
+                        wavelengths.attenuation(&next, attenuated_color)
+                    }
+                    None => attenuated_color,
+                };
+                // coverage (alpha) comes from where the path ends, not from the albedos along it
+                attenuated_color[3] = 1.;
+
+                let mut color = self.ray_color(&new_ray, depth + 1) * attenuated_color;
+                for channel in 0..3 {
+                    color[channel] += emitted[channel];
+                }
+                return color;
+            }
+
+            // if light fully absorbed then only the emitted light is left
+            let mut color = emitted;
+            color[3] = 1.;
+            return color;
+        }
+
+        // only the background seen straight from the camera, not its reflections
+        if depth == 0 && self.scene.transparent_background {
+            return TRANSPARENT;
+        }
+
+        // also reached through direct lighting, weighted so it is not counted twice
+        let environment = &self.scene.environment;
+        let weight = match ray.scattering_pdf {
+            Some(scattering_pdf) => power_heuristic(scattering_pdf, environment.pdf(ray.direction)),
+            None => 1.,
+        };
+        let radiance = environment.radiance(ray.direction) * weight;
+        let mut radiance = match ray.wavelengths {
+            Some(wavelengths) => wavelengths.upsample_light(radiance),
+            None => radiance,
+        };
+        radiance[3] = 1.;
+        radiance
+    }
+
+    /// MIS weight of the light a hit surface emits, emissive spheres are also found by shadow rays
+    fn emission_weight(&self, ray: &Ray, hit_record: &HitRecord) -> f32 {
+        let Some(scattering_pdf) = ray.scattering_pdf else {
+            return 1.; // camera rays and discrete directions are the only way to see the light
+        };
+        let Some((index, light)) = self
+            .scene
+            .lights
+            .iter()
+            .enumerate()
+            .find(|(_, light)| light.object_id() == Some(hit_record.object_id))
+        else {
+            return 1.;
+        };
+
+        match self.light_sampler.probability(index) {
+            Some(probability) => power_heuristic(
+                scattering_pdf,
+                probability * light.pdf(&ray.origin, ray.direction.normalise()),
+            ),
+            None => 0., // only lit through shadow rays, see `direct_light`
+        }
+    }
+
+    /// Cutouts are stochastic, the surface is hit with a chance equal to its opacity
+    fn is_opaque(ray: &Ray, hit_record: &HitRecord) -> bool {
+        let opacity = hit_record.material.opacity(ray, hit_record);
+        opacity >= 1. || rand::thread_rng().gen::<f32>() < opacity
+    }
+
+    /// Whether anything blocks the way from `origin` towards a light `distance` away along `direction`
+    fn is_occluded(&self, origin: Point, direction: Vec4, distance: f32) -> bool {
+        let mut shadow_ray = Ray::new(origin, direction);
+        let mut distance = distance;
+        while let Some(hit_record) = self.closest_hit(&shadow_ray) {
+            if hit_record.t >= distance {
+                return false;
+            }
+            if Self::is_opaque(&shadow_ray, &hit_record) {
+                return true;
+            }
+            // carry on past the cutout, the direction is unit length so t is a distance
+            distance -= hit_record.t;
+            shadow_ray = Ray::new(hit_record.point_of_intersection, direction);
+        }
+        false
+    }
+
+    /**
+    Light reaching the hit straight from the scene's lights and reflected back along the ray. Lights
+    are sampled with shadow rays, picked by the light sampler. Lights that rays can also hit (the
+    environment, emissive spheres) are weighted against the material's sampling (MIS).
+    */
+    fn direct_light(&self, ray: &Ray, hit_record: &HitRecord) -> Color {
+        let mut color = Color::new(0., 0., 0., 1.);
+        let picked = self.light_sampler.sample(
+            &self.scene.lights,
+            &hit_record.point_of_intersection,
+            |sample| {
+                let reflectance = hit_record
+                    .material
+                    .evaluate(ray, hit_record, sample.direction);
+                (reflectance * sample.light).luminance()
+            },
+        );
+        // environments that can be sampled are, once
+        let samples = picked
+            .into_iter()
+            .map(|(index, sample, probability)| {
+                (
+                    sample,
+                    probability,
+                    self.light_sampler.probability(index),
+                )
+            })
+            .chain(
+                self.scene
+                    .environment
+                    .sample()
+                    .map(|sample| (sample, 1., Some(1.))),
+            );
+        for (sample, probability, mis_probability) in samples {
+            let reflectance = hit_record
+                .material
+                .evaluate(ray, hit_record, sample.direction);
+            if reflectance.x() <= 0. && reflectance.y() <= 0. && reflectance.z() <= 0. {
+                continue;
+            }
+            if self.is_occluded(
+                hit_record.point_of_intersection,
+                sample.direction,
+                sample.distance,
+            ) {
+                continue;
             }
 
-            return BLACK; // if light fully absorbed then return black
+            // rays hitting the light are weighed against this with the probability of the pick,
+            // without it they are left out instead (see `emission_weight`)
+            let weight = match mis_probability {
+                Some(mis_probability) if sample.pdf.is_finite() => power_heuristic(
+                    sample.pdf * mis_probability,
+                    hit_record.material.pdf(ray, hit_record, sample.direction),
+                ),
+                _ => 1.,
+            } / probability;
+            let contribution = match ray.wavelengths {
+                Some(wavelengths) => {
+                    wavelengths.upsample(reflectance) * wavelengths.upsample_light(sample.light)
+                }
+                None => reflectance * sample.light,
+            } * weight;
+            for channel in 0..3 {
+                color[channel] += contribution[channel];
+            }
         }
+        color
+    }
 
-        let y = ray.direction.normalise().y(); // -1 <= y <= 1
-        let t = map_to_range(y, -1., 1., 0., 1.);
+    /**
+    Traces a single camera sample, pixels that the camera does not cover are transparent. Rays
+    blocked inside of the camera still cover the pixel, in black, so they are averaged in with the
+    others and darken the pixel by the fraction of the lens that is blocked (optical vignetting).
+    */
+    fn sample(&self, u: f32, v: f32) -> Color {
+        let (ray, weight) = match self.camera.generate_weighted_ray(u, v) {
+            Some((_, weight)) if weight <= 0. => return BLACK,
+            Some(camera_sample) => camera_sample,
+            None => return TRANSPARENT,
+        };
 
-        WHITE * (1. - t) + BLUE * t
+        let mut color = if self.spectral {
+            let wavelengths = Wavelengths::sample(rand::thread_rng().gen());
+            let ray = ray.with_wavelengths(Some(wavelengths));
+            wavelengths.to_rgb(self.ray_color(&ray, 0))
+        } else {
+            self.ray_color(&ray, 0)
+        };
+        // the weight dims the light, not the coverage
+        for channel in 0..3 {
+            color[channel] *= weight;
+        }
+        color
     }
 
     pub fn post_process(&self, pixel_color: Color) -> Color {
         // TODO: Experiment with mutable reference instead of creating a new struct
         // gamma correction (using gamma = 2, ie. p` = p ^ 1/2)
-        let gamma_corrected_pixel_color = Color::new(
+        Color::new(
             pixel_color.x().sqrt(),
             pixel_color.y().sqrt(),
             pixel_color.z().sqrt(),
             pixel_color.w(),
-        );
-        return gamma_corrected_pixel_color;
+        )
     }
 
     pub fn render(&self) -> Vec<Vec<Color>> {
@@ -123,7 +500,8 @@ impl Engine {
             output.push(vec![]);
             for column in 0..self.image_width {
                 let pixel_color = if self.anti_aliasing {
-                    // TODO: Alpha calculation messed up during anti-aliasing
+                    // colors are weighted by their alpha so transparent samples do not darken the
+                    // pixel, the alpha ends up as the coverage of the pixel
                     let mut temp_pixel_color = Color::new(0., 0., 0., 0.);
                     for _ in 0..self.anti_aliasing_sample_count {
                         // INFO: Minor improvement by adding -0.5
@@ -131,17 +509,25 @@ impl Engine {
                             (column as f32 + (rng.gen::<f32>() - 0.5)) / self.image_width as f32;
                         let v = (row as f32 + (rng.gen::<f32>() - 0.5)) / self.image_height as f32;
 
-                        let ray = self.camera.generate_ray(u, v);
-                        temp_pixel_color += self.ray_color(&ray, 0);
+                        let sample = self.sample(u, v);
+                        temp_pixel_color += Color::new(
+                            sample.x() * sample.w(),
+                            sample.y() * sample.w(),
+                            sample.z() * sample.w(),
+                            sample.w(),
+                        );
+                    }
+                    let coverage = temp_pixel_color.w();
+                    if coverage > 0. {
+                        temp_pixel_color /= coverage;
                     }
-                    temp_pixel_color /= self.anti_aliasing_sample_count as f32;
+                    temp_pixel_color[3] = coverage / self.anti_aliasing_sample_count as f32;
                     temp_pixel_color
                 } else {
                     let u = column as f32 / self.image_width as f32;
                     let v = row as f32 / self.image_height as f32;
 
-                    let ray = self.camera.generate_ray(u, v);
-                    self.ray_color(&ray, 0)
+                    self.sample(u, v)
                 };
 
                 let processed_pixel_color = self.post_process(pixel_color);
@@ -154,3 +540,286 @@ impl Engine {
         output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cameras::perspective_camera::PerspectiveCamera,
+        environments::{environment_map::EnvironmentMap, solid_environment::SolidEnvironment},
+        lights::point_light::PointLight,
+        materials::{
+            dielectric::Dielectric, emissive::Emissive, lambertian::Lambertian, layered::Layered,
+            metal::Metal, mix::MixMaterial,
+        },
+        objects::sphere::Sphere,
+        ray_tracer::interface::material_base::Material,
+        textures::solid_color::SolidColor,
+    };
+    use std::rc::Rc;
+
+    /// Anti-aliased render of a sphere filling the view over a transparent background
+    fn render_sphere(opacity: f32) -> Vec<Vec<Color>> {
+        let mut scene = Scene::new();
+        scene.set_transparent_background(true);
+        let opacity = Rc::new(SolidColor::new(Color::new(
+            opacity, opacity, opacity, 1.,
+        )));
+        scene.add(Box::new(
+            Sphere::new(
+                1.,
+                Point::new(0., 0., -3., 0.),
+                Rc::new(Lambertian::new(WHITE)),
+            )
+            .with_opacity(opacity),
+        ));
+
+        let camera = PerspectiveCamera::new(
+            1.,
+            1.,
+            20.,
+            Point::new(0., 0., 0., 0.),
+            Point::new(0., 0., -1., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        );
+        Engine::new(Box::new(camera), scene, 4, 4, true, 16).render()
+    }
+
+    /// Camera looking at the origin from 3 units away, seeing a sphere of radius 1 there fill the view
+    fn camera() -> PerspectiveCamera {
+        PerspectiveCamera::new(
+            1.,
+            1.,
+            20.,
+            Point::new(0., 0., 3., 0.),
+            Point::new(0., 0., 0., 0.),
+            Vec4::new(0., 1., 0., 0.),
+        )
+    }
+
+    #[test]
+    fn cut_out_object_is_transparent() {
+        for pixel in render_sphere(0.).iter().flatten() {
+            assert_eq!(pixel.w(), 0.);
+        }
+        for pixel in render_sphere(1.).iter().flatten() {
+            assert_eq!(pixel.w(), 1.);
+        }
+    }
+
+    #[test]
+    fn lambertian_sphere_in_a_constant_environment_map_has_its_albedo() {
+        // the map is importance sampled, so the light is found both by shadow rays and by
+        // scattered rays and weighted between them
+        let albedo = 0.5;
+        let mut scene = Scene::new();
+        scene.set_environment(Box::new(EnvironmentMap::from_fn(32, 16, |_| {
+            WHITE
+        })));
+        scene.add(Box::new(Sphere::new(
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Rc::new(Lambertian::new(Color::new(
+                albedo, albedo, albedo, 1.,
+            ))),
+        )));
+
+        let engine = Engine::new(Box::new(camera()), scene, 4, 4, true, 256);
+        let pixels: Vec<Color> = engine.render().into_iter().flatten().collect();
+        // undo the gamma correction
+        let mean = pixels
+            .iter()
+            .map(|pixel| pixel.x() * pixel.x())
+            .sum::<f32>() /
+            pixels.len() as f32;
+        assert!(
+            (mean - albedo).abs() < 0.01,
+            "mean radiance {}, expected {}",
+            mean,
+            albedo
+        );
+    }
+
+    /// Mean radiance of a Lambertian sphere lit by an emissive sphere, found by shadow rays or not
+    fn render_sphere_lit_by_sphere(
+        sampled: bool,
+        selection: LightSelection,
+        sample_count: u32,
+    ) -> f32 {
+        let mut scene = Scene::new();
+        scene.set_environment(Box::new(SolidEnvironment::new(BLACK)));
+        scene.add(Box::new(Sphere::new(
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Rc::new(Lambertian::new(WHITE)),
+        )));
+        let lamp = Sphere::new(
+            1.,
+            Point::new(2., 2., 2., 0.),
+            Rc::new(Emissive::new(WHITE, 2.)),
+        );
+        if sampled {
+            scene.add_sphere_light(lamp);
+        } else {
+            scene.add(Box::new(lamp));
+        }
+
+        let engine = Engine::new(
+            Box::new(camera()),
+            scene,
+            4,
+            4,
+            true,
+            sample_count,
+        )
+        .with_light_selection(selection);
+        let pixels: Vec<Color> = engine.render().into_iter().flatten().collect();
+        // undo the gamma correction
+        pixels
+            .iter()
+            .map(|pixel| pixel.x() * pixel.x())
+            .sum::<f32>() /
+            pixels.len() as f32
+    }
+
+    #[test]
+    fn sphere_lights_are_not_counted_twice() {
+        // only scattered rays find the light, which is a lot noisier
+        let expected = render_sphere_lit_by_sphere(false, LightSelection::All, 16384);
+        for selection in [LightSelection::All, LightSelection::Contribution] {
+            let radiance = render_sphere_lit_by_sphere(true, selection, 1024);
+            assert!(
+                (radiance - expected).abs() < 0.05 * expected,
+                "{} != {}",
+                radiance,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn point_lit_layered_and_mixed_surfaces_are_not_black() {
+        let diffuse: Rc<dyn Material> = Rc::new(Lambertian::new(WHITE));
+        let materials: [Rc<dyn Material>; 2] = [
+            Rc::new(Layered::new(Rc::clone(&diffuse), 1.5)),
+            Rc::new(MixMaterial::new(
+                diffuse,
+                Rc::new(Metal::new(WHITE, None)),
+                0.5,
+            )),
+        ];
+        for material in materials {
+            // rays scattered off the surface never find the point light, only shadow rays do
+            let mut scene = Scene::new();
+            scene.set_environment(Box::new(SolidEnvironment::new(BLACK)));
+            scene.add(Box::new(Sphere::new(
+                1.,
+                Point::new(0., 0., 0., 0.),
+                material,
+            )));
+            scene.add_light(Box::new(PointLight::new(
+                Point::new(0., 0., 3., 0.),
+                WHITE,
+                10.,
+            )));
+
+            let engine = Engine::new(Box::new(camera()), scene, 4, 4, true, 4);
+            for pixel in engine.render().iter().flatten() {
+                assert!(pixel.x() > 0., "black pixel {:?}", pixel.e);
+            }
+        }
+    }
+
+    #[test]
+    fn non_absorbing_medium_lets_all_light_through() {
+        // index matched so nothing reflects off the boundary, green does not interact at all
+        let scattering = Scattering {
+            extinction: Color::new(1., 0., 1., 1.),
+            albedo: WHITE,
+            anisotropy: 0.,
+        };
+        let mut scene = Scene::new();
+        scene.set_environment(Box::new(SolidEnvironment::new(WHITE)));
+        scene.add(Box::new(Sphere::new(
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Rc::new(Dielectric::new(WHITE, 1.).with_scattering(scattering)),
+        )));
+
+        let engine = Engine::new(Box::new(camera()), scene, 4, 4, true, 1024);
+        let pixels: Vec<Color> = engine.render().into_iter().flatten().collect();
+        for channel in 0..3 {
+            let mean = pixels.iter().map(|pixel| pixel[channel]).sum::<f32>() / pixels.len() as f32;
+            assert!(
+                (mean - 1.).abs() < 0.03,
+                "channel {}: mean throughput {}",
+                channel,
+                mean
+            );
+        }
+    }
+
+    /// Radiance along a ray through the center of the scene, in a white environment
+    fn radiance_through_center(scene: Scene) -> Color {
+        let engine = Engine::new(Box::new(camera()), scene, 1, 1, false, 1);
+        let ray = Ray::new(
+            Point::new(0., 0., 3., 0.),
+            Vec4::new(0., 0., -1., 0.),
+        );
+        engine.ray_color(&ray, 0)
+    }
+
+    fn assert_transmittance(color: Color, expected: [f32; 3]) {
+        for channel in 0..3 {
+            assert!(
+                (color[channel] - expected[channel]).abs() < 1e-4,
+                "transmittance {:?}, expected {:?}",
+                color.e,
+                expected
+            );
+        }
+    }
+
+    /// Index matched glass, so the ray goes straight through the 2 units across the sphere
+    #[test]
+    fn absorbing_glass_follows_beer_lambert() {
+        let absorption = Color::new(0.5, 1., 2., 0.);
+        let mut scene = Scene::new();
+        scene.set_environment(Box::new(SolidEnvironment::new(WHITE)));
+        scene.add(Box::new(Sphere::new(
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Rc::new(Dielectric::new(WHITE, 1.).with_absorption(absorption)),
+        )));
+
+        let expected = [0, 1, 2].map(|channel| (-absorption[channel] * 2.).exp());
+        assert_transmittance(radiance_through_center(scene), expected);
+    }
+
+    /// Water in a glass, the glass absorbs only where the ray is not in the water
+    #[test]
+    fn nested_media_absorb_over_their_own_segments() {
+        let glass = Color::new(0.5, 0.5, 0.5, 0.);
+        let water = Color::new(0., 1., 2., 0.);
+        let mut scene = Scene::new();
+        scene.set_environment(Box::new(SolidEnvironment::new(WHITE)));
+        scene.add(Box::new(Sphere::new(
+            1.,
+            Point::new(0., 0., 0., 0.),
+            Rc::new(Dielectric::new(WHITE, 1.).with_absorption(glass)),
+        )));
+        scene.add(Box::new(Sphere::new(
+            0.5,
+            Point::new(0., 0., 0., 0.),
+            Rc::new(
+                Dielectric::new(WHITE, 1.)
+                    .with_priority(1)
+                    .with_absorption(water),
+            ),
+        )));
+
+        // 0.5 + 0.5 units of glass, 1 unit of water
+        let expected = [0, 1, 2].map(|channel| (-glass[channel] - water[channel]).exp());
+        assert_transmittance(radiance_through_center(scene), expected);
+    }
+}
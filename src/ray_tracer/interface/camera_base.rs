@@ -11,7 +11,22 @@ pub trait Camera {
 
     Returns:
 
-    A Ray
+    A Ray, or None if the point does not map to any direction (eg: outside the fisheye circle).
+    Such pixels are rendered transparent.
     */
-    fn generate_ray(&self, u: f32, v: f32) -> Ray;
+    fn generate_ray(&self, u: f32, v: f32) -> Option<Ray>;
+
+    /**
+    Generates a ray like [`Camera::generate_ray`], along with the fraction of the light arriving
+    along it that reaches the film (eg: the falloff and vignetting of a real lens). Cameras that do
+    not block or dim any light can keep the default weight of 1.
+
+    Returns:
+
+    The ray and its weight, or None if the point does not map to any direction. Rays with a weight
+    of 0 are blocked inside of the camera and are rendered black (not transparent).
+    */
+    fn generate_weighted_ray(&self, u: f32, v: f32) -> Option<(Ray, f32)> {
+        self.generate_ray(u, v).map(|ray| (ray, 1.))
+    }
 }
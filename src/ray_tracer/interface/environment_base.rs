@@ -0,0 +1,34 @@
+use super::light_base::LightSample;
+use crate::utils::vec4::{Color, Vec4};
+
+/// Light arriving from infinitely far away (eg: the sky), seen by rays that miss every object
+pub trait Environment {
+    /**
+    Looks up the light arriving from a direction.
+
+    - `direction`: direction the light arrives from, the opposite of the way it travels
+
+    Returns:
+
+    The radiance, alpha is 1
+    */
+    fn radiance(&self, direction: Vec4) -> Color;
+
+    /**
+    Samples a direction the environment lights the scene from, for shadow rays (direct lighting).
+    Only worth it for environments with bright spots (eg: the sun) that can sample them, by
+    default the environment is only found by the rays the materials scatter.
+
+    Returns:
+
+    The sample at an infinite distance, or None if no light was found
+    */
+    fn sample(&self) -> Option<LightSample> {
+        None
+    }
+
+    /// Probability density of `sample` picking `direction`, per unit solid angle
+    fn pdf(&self, _direction: Vec4) -> f32 {
+        0.
+    }
+}
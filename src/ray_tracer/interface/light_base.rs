@@ -0,0 +1,49 @@
+use crate::utils::vec4::{Color, Point, Vec4};
+
+/// Light arriving at a point from a light source
+pub struct LightSample {
+    pub direction: Vec4, // unit vector from the point towards the light
+    pub distance: f32,   // to the light, infinite for directional lights
+    pub light: Color, // incoming light, already divided by the probability of picking this sample
+    pub pdf: f32,     // per unit solid angle, infinite for lights rays can't hit (eg: point lights)
+}
+
+pub trait Light {
+    /**
+    Samples the light arriving at a point, used for shadow rays (direct lighting).
+
+    - `point`: the point being lit, in world space
+
+    Returns:
+
+    The sample, or None if no light arrives at the point (eg: outside of a spotlight's cone)
+    */
+    fn sample(&self, point: &Point) -> Option<LightSample>;
+
+    /**
+    Rough total luminance given off by the light, used to pick lights in scenes with many of them.
+    Only its ratio to the power of the other lights matters.
+
+    Returns:
+
+    The power, 0 or more
+    */
+    fn power(&self) -> f32;
+
+    /**
+    Density of `sample` picking `direction` from `point`, per unit solid angle. Only lights that
+    rays can hit (eg: emissive spheres) have one, it weighs them against the materials (MIS).
+
+    Returns:
+
+    The density, 0 if `sample` never picks the direction
+    */
+    fn pdf(&self, _point: &Point, _direction: Vec4) -> f32 {
+        0.
+    }
+
+    /// Object rays hit when they find the light (see HitRecord::object_id), None for lights rays can't hit
+    fn object_id(&self) -> Option<usize> {
+        None
+    }
+}
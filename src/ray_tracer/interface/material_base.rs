@@ -1,6 +1,27 @@
-use crate::utils::vec4::Color;
+use crate::utils::vec4::{Color, Vec4};
 use super::{super::utils::Ray, object_base::HitRecord};
 
 pub trait Material {
   fn generate_reflected_ray(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)>;
+
+  /// Fraction of the light arriving from `direction` that is scattered back along the ray (BRDF times cosine).
+  /// Black for materials that only scatter into discrete directions (mirrors, glass).
+  fn evaluate(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: Vec4) -> Color {
+    Color::new(0., 0., 0., 1.)
+  }
+
+  /// Light given off by the surface along the ray. Per wavelength on spectral rays (`ray.wavelengths`), RGB otherwise.
+  fn emitted(&self, _ray: &Ray, _hit_record: &HitRecord) -> Color {
+    Color::new(0., 0., 0., 1.)
+  }
+
+  /// Probability density (per unit solid angle) of `generate_reflected_ray` scattering into `direction`
+  fn pdf(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: Vec4) -> f32 {
+    0.
+  }
+
+  /// Chance of a ray stopping at the surface, rays that do not carry on through it (cutouts). 1 for solid surfaces.
+  fn opacity(&self, _ray: &Ray, _hit_record: &HitRecord) -> f32 {
+    1.
+  }
 }
\ No newline at end of file
@@ -1,3 +1,7 @@
 pub mod object_base;
 pub mod camera_base;
-pub mod material_base;
\ No newline at end of file
+pub mod material_base;
+pub mod normal_map_base;
+pub mod texture_base;
+pub mod light_base;
+pub mod environment_base;
\ No newline at end of file
@@ -0,0 +1,15 @@
+use super::object_base::HitRecord;
+use crate::utils::vec4::Vec4;
+
+pub trait NormalMap {
+    /**
+    Computes the shading normal for a hit, eg: from a normal map or a bump map.
+
+    - `hit_record`: the hit, with the (unperturbed) normal and the tangents (dpdu, dpdv)
+
+    Returns:
+
+    The perturbed unit normal, on the same side of the surface as `hit_record.geometric_normal`
+    */
+    fn perturb(&self, hit_record: &HitRecord) -> Vec4;
+}
@@ -1,13 +1,61 @@
-use std::rc::Rc;
+use std::{
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use crate::ray_tracer::utils::Ray;
-use crate::utils::vec4::{Point, Vec4};
-use super::material_base::Material;
+use crate::utils::{frame::Frame, vec4::{Point, Vec4}};
+use super::{material_base::Material, normal_map_base::NormalMap};
 
 pub struct HitRecord {
+    // shading normal, perturbed by the normal map if there is one
     pub normal: Vec4,
+    // normal of the actual surface, tells which side of the surface a ray is on
+    pub geometric_normal: Vec4,
     pub point_of_intersection: Point,
     pub t: f32,
-    pub material: Rc<dyn Material> 
+    // surface co-ordinates of the hit, domain => [0, 1]
+    pub u: f32,
+    pub v: f32,
+    // partial derivatives of the point along u and v (tangent and bitangent), not normalised
+    pub dpdu: Vec4,
+    pub dpdv: Vec4,
+    pub material: Rc<dyn Material>,
+    // identifies the object that was hit, materials can be shared between objects
+    pub object_id: usize,
+    // perturbs the shading normal before the material is evaluated
+    pub normal_map: Option<Rc<dyn NormalMap>>,
+}
+
+impl HitRecord {
+    /// Orthonormal shading frame at the hit, the tangent follows dpdu where it exists so
+    /// anisotropic materials and tangent space maps line up with the surface co-ordinates
+    pub fn tangent_frame(&self) -> Frame {
+        let normal = self.normal;
+        let tangent = self.dpdu - normal * normal.dot(self.dpdu);
+        if tangent.is_degenerate() {
+            // eg: at the poles of a sphere
+            return Frame::from_normal(normal);
+        }
+
+        let tangent = tangent.normalise();
+        let bitangent = normal.cross(tangent);
+        Frame {
+            tangent,
+            // keep the bitangent pointing along v, even on flipped normals
+            bitangent: if bitangent.dot(self.dpdv) < 0. {
+                -bitangent
+            } else {
+                bitangent
+            },
+            normal,
+        }
+    }
+}
+
+/// Unique id for a new object, see [`HitRecord::object_id`]
+pub fn next_object_id() -> usize {
+    static NEXT_OBJECT_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 pub trait Object {
@@ -0,0 +1,15 @@
+use crate::utils::vec4::{Color, Point};
+
+pub trait Texture {
+    /**
+    Looks up the color of the texture at a point on a surface.
+
+    - `u`, `v`: surface co-ordinates of the point; domain => [0, 1]
+    - `point`: the point in world space, used by solid (3D) textures
+
+    Returns:
+
+    The color at that point
+    */
+    fn value(&self, u: f32, v: f32, point: &Point) -> Color;
+}
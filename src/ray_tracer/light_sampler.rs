@@ -0,0 +1,255 @@
+use super::interface::light_base::{Light, LightSample};
+use crate::utils::{distribution::Distribution1D, vec4::Point};
+use rand::prelude::*;
+use std::cell::RefCell;
+
+/// How the lights of a scene are picked for direct lighting at each hit
+#[derive(Clone, Copy)]
+pub enum LightSelection {
+    /// Every light, best for scenes with only a few lights
+    All,
+    /// A single light, each one as likely as the others
+    Uniform,
+    /// A single light, picked proportional to its power (see [`Light::power`])
+    Power,
+    /**
+    A single light, picked proportional to its unshadowed contribution at the hit. Every light is
+    sampled but only the picked one gets a shadow ray, so nearby and facing lights are picked far
+    more often than with `Power`. Lights rays can hit (eg: emissive spheres) are then only found by
+    shadow rays, the probability of picking them is not known where a ray hits them.
+    */
+    Contribution,
+}
+
+/**
+Picks lights for shadow rays (direct lighting) and keeps count of how many samples each one gave,
+for debugging scenes with many lights. Picked samples are returned with the probability of
+picking them, so their light has to be divided by it.
+*/
+pub struct LightSampler {
+    selection: LightSelection,
+    power: Option<Distribution1D>, // None without lights
+    counts: RefCell<Vec<u64>>,
+}
+
+impl LightSampler {
+    pub fn new(selection: LightSelection, lights: &[Box<dyn Light>]) -> Self {
+        Self {
+            selection,
+            power: Distribution1D::new(lights.iter().map(|light| light.power()).collect()),
+            counts: RefCell::new(vec![0; lights.len()]),
+        }
+    }
+
+    /**
+    Picks lights to light a point with.
+
+    - `lights`: the lights of the scene, the same ones the sampler was made with
+    - `point`: the point being lit
+    - `contribution`: how much light a sample would reflect (eg: its luminance after the material),
+      only called for `LightSelection::Contribution`
+
+    Returns:
+
+    The picked samples, each with the index of its light and the probability it was picked with
+    */
+    pub fn sample(
+        &self,
+        lights: &[Box<dyn Light>],
+        point: &Point,
+        contribution: impl Fn(&LightSample) -> f32,
+    ) -> Vec<(usize, LightSample, f32)> {
+        if lights.is_empty() {
+            return vec![];
+        }
+
+        let mut rng = rand::thread_rng();
+        let (index, sample, probability) = match self.selection {
+            LightSelection::All => {
+                let mut counts = self.counts.borrow_mut();
+                return lights
+                    .iter()
+                    .zip(counts.iter_mut())
+                    .enumerate()
+                    .filter_map(|(index, (light, count))| {
+                        let sample = light.sample(point)?;
+                        *count += 1;
+                        Some((index, sample, 1.))
+                    })
+                    .collect();
+            }
+            LightSelection::Uniform => {
+                let index = rng.gen_range(0..lights.len());
+                (
+                    index,
+                    lights[index].sample(point),
+                    1. / lights.len() as f32,
+                )
+            }
+            LightSelection::Power => {
+                let Some(power) = &self.power else {
+                    return vec![];
+                };
+                let (index, probability) = power.sample_discrete(rng.gen());
+                (index, lights[index].sample(point), probability)
+            }
+            LightSelection::Contribution => {
+                let samples: Vec<(usize, LightSample, f32)> = lights
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, light)| {
+                        let sample = light.sample(point)?;
+                        let weight = contribution(&sample).max(0.);
+                        (weight > 0.).then_some((index, sample, weight))
+                    })
+                    .collect();
+                let total: f32 = samples.iter().map(|(_, _, weight)| weight).sum();
+                if total <= 0. {
+                    return vec![];
+                }
+
+                // resampling, unbiased since every light that could contribute can be picked
+                let mut target = rng.gen::<f32>() * total;
+                let mut picked = samples.len() - 1;
+                for (i, (_, _, weight)) in samples.iter().enumerate() {
+                    if target < *weight {
+                        picked = i;
+                        break;
+                    }
+                    target -= weight;
+                }
+                let (index, sample, weight) = samples.into_iter().nth(picked).unwrap();
+                (index, Some(sample), weight / total)
+            }
+        };
+
+        match sample {
+            Some(sample) if probability > 0. => {
+                self.counts.borrow_mut()[index] += 1;
+                vec![(index, sample, probability)]
+            }
+            _ => vec![],
+        }
+    }
+
+    /**
+    Probability of `sample` picking the light at `index`, needed to weigh lights that rays can hit
+    against the materials (MIS).
+
+    Returns:
+
+    The probability, or None for `Contribution` where it depends on the samples of all the lights
+    */
+    pub fn probability(&self, index: usize) -> Option<f32> {
+        match self.selection {
+            LightSelection::All => Some(1.),
+            LightSelection::Uniform => Some(1. / self.counts.borrow().len() as f32),
+            LightSelection::Power => self
+                .power
+                .as_ref()
+                .map(|power| power.discrete_probability(index)),
+            LightSelection::Contribution => None,
+        }
+    }
+
+    /// How many samples each light gave so far, in the order of the scene's lights. Picked lights
+    /// that could not light the point (eg: a spot light facing away) are not counted.
+    pub fn counts(&self) -> Vec<u64> {
+        self.counts.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lights::point_light::PointLight, utils::vec4::Color};
+
+    const SAMPLE_COUNT: u32 = 50_000;
+
+    /// A row of lights, one of them a lot brighter than the others
+    fn lights() -> Vec<Box<dyn Light>> {
+        (0..8)
+            .map(|i| {
+                let intensity = if i == 5 { 50. } else { 1. + i as f32 };
+                Box::new(PointLight::new(
+                    Point::new(i as f32, 1., 0., 1.),
+                    Color::new(1., 1., 1., 1.),
+                    intensity,
+                )) as Box<dyn Light>
+            })
+            .collect()
+    }
+
+    /// Light arriving at the origin, summed over the picked samples
+    fn estimate(selection: LightSelection) -> (f32, Vec<u64>) {
+        let lights = lights();
+        let sampler = LightSampler::new(selection, &lights);
+        let point = Point::new(0., 0., 0., 1.);
+
+        let mut sum = 0.;
+        for _ in 0..SAMPLE_COUNT {
+            for (_, sample, probability) in
+                sampler.sample(&lights, &point, |sample| sample.light.y())
+            {
+                sum += sample.light.y() / probability;
+            }
+        }
+        (sum / SAMPLE_COUNT as f32, sampler.counts())
+    }
+
+    #[test]
+    fn every_strategy_gives_the_same_light() {
+        let (expected, counts) = estimate(LightSelection::All);
+        assert!(counts.iter().all(|count| *count == SAMPLE_COUNT as u64));
+
+        for selection in [
+            LightSelection::Uniform,
+            LightSelection::Power,
+            LightSelection::Contribution,
+        ] {
+            let (estimate, counts) = estimate(selection);
+            assert_eq!(counts.iter().sum::<u64>(), SAMPLE_COUNT as u64);
+            assert!(
+                (estimate - expected).abs() < 0.05 * expected,
+                "{} != {}",
+                estimate,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn power_picks_bright_lights_more_often() {
+        let (_, counts) = estimate(LightSelection::Power);
+        assert!(counts[5] > counts[4] * 5);
+    }
+
+    #[test]
+    fn lights_without_a_sample_are_not_counted() {
+        // a point light at the point being lit has no direction to light it from
+        let point = Point::new(0., 0., 0., 1.);
+        let lights: Vec<Box<dyn Light>> = vec![
+            Box::new(PointLight::new(
+                point,
+                Color::new(1., 1., 1., 1.),
+                1.,
+            )),
+            Box::new(PointLight::new(
+                Point::new(0., 1., 0., 1.),
+                Color::new(1., 1., 1., 1.),
+                1.,
+            )),
+        ];
+        for selection in [
+            LightSelection::All,
+            LightSelection::Uniform,
+            LightSelection::Power,
+        ] {
+            let sampler = LightSampler::new(selection, &lights);
+            for _ in 0..100 {
+                sampler.sample(&lights, &point, |sample| sample.light.y());
+            }
+            assert_eq!(sampler.counts()[0], 0);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::utils::{
+    frame::Frame,
+    vec4::{Color, Vec4},
+};
+use rand::prelude::*;
+use std::{f32::consts::PI, rc::Rc};
+
+/// Participating medium filling a volume: light travels a random distance through it before being scattered or absorbed
+#[derive(Clone, Copy)]
+pub struct Scattering {
+    pub extinction: Color, // chance per unit distance of an interaction, per channel (1 / mean free path)
+    pub albedo: Color,     // chance of an interaction scattering the light instead of absorbing it
+    pub anisotropy: f32,   // Henyey-Greenstein g, > 0 scatters forwards and < 0 backwards
+}
+
+impl Scattering {
+    /// New direction for light travelling along `direction` that gets scattered, sampled from the phase function
+    pub fn sample_direction(&self, direction: Vec4) -> Vec4 {
+        let mut rng = rand::thread_rng();
+        let g = self.anisotropy;
+        let u = rng.gen::<f32>();
+        let cos_theta = if g.abs() < 1e-3 {
+            1. - 2. * u
+        } else {
+            let s = (1. - g * g) / (1. - g + 2. * g * u);
+            ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * rng.gen::<f32>();
+
+        Frame::from_normal(direction).to_world(Vec4::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+            0.,
+        ))
+    }
+}
+
+/// A dielectric volume a ray can be inside of
+#[derive(Clone, Copy)]
+pub struct Medium {
+    pub id: usize, // identifies the object the medium fills, see HitRecord::object_id
+    pub refractive_index: f32,
+    pub priority: u32, // where media overlap, the one with the highest priority wins
+    pub scattering: Option<Scattering>,
+    pub absorption: Color, // Beer-Lambert absorption coefficient per unit distance, per channel
+}
+
+/// Media a ray is currently inside of, in the order they were entered. Rays start in air.
+///
+/// Stacks are immutable lists shared between rays, so every bounce can carry its stack on without
+/// copying it. Only entering or leaving a medium builds a new one.
+#[derive(Clone, Default)]
+pub struct MediumStack {
+    top: Option<Rc<Entry>>, // the medium entered last
+}
+
+struct Entry {
+    medium: Medium,
+    below: Option<Rc<Entry>>,
+}
+
+impl MediumStack {
+    /// Media from the one entered last to the one entered first
+    fn iter(&self) -> impl Iterator<Item = &Medium> {
+        let mut entry = self.top.as_deref();
+        std::iter::from_fn(move || {
+            let current = entry?;
+            entry = current.below.as_deref();
+            Some(&current.medium)
+        })
+    }
+
+    /// The medium the ray is actually travelling through, None for air
+    pub fn current(&self) -> Option<&Medium> {
+        // ties go to the medium entered last
+        self.iter().reduce(|current, medium| {
+            if medium.priority > current.priority {
+                medium
+            } else {
+                current
+            }
+        })
+    }
+
+    pub fn refractive_index(&self) -> f32 {
+        self.current().map_or(1., |medium| medium.refractive_index)
+    }
+
+    /// Whether `medium` is overlapped by a medium with a higher priority, its boundary then has no effect
+    pub fn is_overridden(&self, medium: &Medium) -> bool {
+        self.current()
+            .is_some_and(|current| current.priority > medium.priority)
+    }
+
+    /// Stack after the ray enters `medium`
+    pub fn entered(&self, medium: Medium) -> Self {
+        Self {
+            top: Some(Rc::new(Entry {
+                medium,
+                below: self.top.clone(),
+            })),
+        }
+    }
+
+    /// Stack after the ray leaves the medium `id`, if the ray was never inside it nothing changes
+    pub fn exited(&self, id: usize) -> Self {
+        let mut above = vec![];
+        let mut entry = self.top.as_ref();
+        while let Some(current) = entry {
+            if current.medium.id == id {
+                // the media entered after it are stacked back on top of the ones before it
+                let mut stack = Self {
+                    top: current.below.clone(),
+                };
+                for medium in above.into_iter().rev() {
+                    stack = stack.entered(medium);
+                }
+                return stack;
+            }
+            above.push(current.medium);
+            entry = current.below.as_ref();
+        }
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLASS: usize = 0;
+    const WATER: usize = 1;
+
+    fn medium(id: usize, refractive_index: f32, priority: u32) -> Medium {
+        Medium {
+            id,
+            refractive_index,
+            priority,
+            scattering: None,
+            absorption: Color::new(0., 0., 0., 0.),
+        }
+    }
+
+    // water (priority 2) filling a glass (priority 1), the water overlaps the inner wall slightly
+    fn glass() -> Medium {
+        medium(GLASS, 1.5, 1)
+    }
+
+    fn water() -> Medium {
+        medium(WATER, 1.33, 2)
+    }
+
+    #[test]
+    fn refractive_index_is_the_one_of_the_medium_with_the_highest_priority() {
+        let air = MediumStack::default();
+        assert!(air.current().is_none());
+        assert_eq!(air.refractive_index(), 1.);
+
+        let in_glass = air.entered(glass());
+        assert_eq!(in_glass.refractive_index(), 1.5);
+        let in_water = in_glass.entered(water());
+        assert_eq!(in_water.refractive_index(), 1.33);
+        assert_eq!(in_water.exited(WATER).refractive_index(), 1.5);
+    }
+
+    #[test]
+    fn lower_priority_surfaces_inside_are_skipped() {
+        // the water overlaps the inner wall of the glass, so the ray meets the water surface first
+        // and then the inner wall of the glass while inside the water
+        let in_water = MediumStack::default().entered(glass()).entered(water());
+        let remaining = in_water.exited(GLASS);
+        assert!(remaining.is_overridden(&glass()));
+        assert_eq!(remaining.refractive_index(), 1.33);
+
+        // entering the glass from the water is skipped as well
+        let in_water = MediumStack::default().entered(water());
+        assert!(in_water.is_overridden(&glass()));
+        let in_glass = MediumStack::default().entered(glass());
+        assert!(!in_glass.is_overridden(&water()));
+    }
+
+    #[test]
+    fn ties_go_to_the_medium_entered_last() {
+        let stack = MediumStack::default()
+            .entered(medium(GLASS, 1.5, 1))
+            .entered(medium(WATER, 1.33, 1));
+        assert_eq!(stack.current().unwrap().id, WATER);
+    }
+
+    #[test]
+    fn exiting_keeps_the_order_of_the_other_media() {
+        let stack = MediumStack::default()
+            .entered(medium(0, 1.1, 0))
+            .entered(medium(1, 1.2, 0))
+            .entered(medium(2, 1.3, 0));
+        let ids = |stack: &MediumStack| stack.iter().map(|medium| medium.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(&stack.exited(1)), [2, 0]);
+        assert_eq!(ids(&stack.exited(0)), [2, 1]);
+        // leaving a medium the ray was never inside of changes nothing
+        assert_eq!(ids(&stack.exited(3)), [2, 1, 0]);
+        // the original stack is shared, not modified
+        assert_eq!(ids(&stack), [2, 1, 0]);
+    }
+}
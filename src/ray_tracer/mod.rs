@@ -1,3 +1,6 @@
 pub mod engine;
+pub mod medium;
+pub mod spectrum;
 pub mod utils;
-pub mod interface;
\ No newline at end of file
+pub mod interface;
+pub mod light_sampler;
\ No newline at end of file
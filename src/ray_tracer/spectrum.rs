@@ -0,0 +1,385 @@
+use crate::utils::vec4::Color;
+use std::sync::OnceLock;
+
+// visible range covered by the upsampling spectra, in nm
+pub const LAMBDA_MIN: f32 = 380.;
+pub const LAMBDA_MAX: f32 = 720.;
+
+const WAVELENGTH_COUNT: usize = 3;
+const LUMINOUS_EFFICACY: f32 = 683.; // lm / W at 555 nm, photometric units to radiometric ones
+
+// white light is daylight (D65, the white of sRGB), approximated by a blackbody
+const WHITE_TEMPERATURE: f32 = 6504.;
+
+// Smits' basis spectra, 10 bins evenly spread over the visible range
+const WHITE_SPECTRUM: [f32; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const CYAN_SPECTRUM: [f32; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const MAGENTA_SPECTRUM: [f32; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const YELLOW_SPECTRUM: [f32; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const RED_SPECTRUM: [f32; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const GREEN_SPECTRUM: [f32; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const BLUE_SPECTRUM: [f32; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/**
+Wavelengths carried by a path (hero wavelength sampling). The hero wavelength is sampled uniformly
+and the others are spread evenly over the visible range from it, so a single path estimates
+several wavelengths at once.
+
+While a path is spectral its colors hold one value per wavelength in x, y, z instead of RGB, the
+alpha channel is untouched.
+*/
+#[derive(Clone, Copy)]
+pub struct Wavelengths {
+    lambda: [f32; WAVELENGTH_COUNT], // in nm, the first one is the hero
+    collapsed: bool,                 // only the hero wavelength is still carried
+}
+
+impl Wavelengths {
+    /// `u` is a uniform random number in [0, 1)
+    pub fn sample(u: f32) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [0.; WAVELENGTH_COUNT];
+        for (i, wavelength) in lambda.iter_mut().enumerate() {
+            let offset = (u + i as f32 / WAVELENGTH_COUNT as f32).fract();
+            *wavelength = LAMBDA_MIN + offset * range;
+        }
+
+        Self {
+            lambda,
+            collapsed: false,
+        }
+    }
+
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Drops all but the hero wavelength, needed once the path depends on the wavelength (eg: dispersion)
+    pub fn collapse(mut self) -> Self {
+        self.collapsed = true;
+        self
+    }
+
+    /// Values of a spectrum at the carried wavelengths
+    pub fn evaluate(&self, spectrum: impl Fn(f32) -> f32) -> Color {
+        let mut spectral = Color::new(0., 0., 0., 1.);
+        for (i, &wavelength) in self.lambda.iter().enumerate() {
+            spectral[i] = spectrum(wavelength);
+        }
+        spectral
+    }
+
+    /// Spectral values of an RGB reflectance (or any other ratio, eg: albedos) at the carried wavelengths
+    pub fn upsample(&self, color: Color) -> Color {
+        let mut spectral = self.evaluate(|wavelength| rgb_to_spectrum(color, wavelength));
+        spectral[3] = color.w();
+        spectral
+    }
+
+    /// Spectral radiance of an RGB light at the carried wavelengths, white light is daylight
+    pub fn upsample_light(&self, color: Color) -> Color {
+        let mut spectral = self
+            .evaluate(|wavelength| rgb_to_spectrum(color, wavelength) * white_light(wavelength));
+        spectral[3] = color.w();
+        spectral
+    }
+
+    /**
+    Spectral attenuation for a bounce that continues the path with `next`. When the path collapses
+    here, the secondary wavelengths can no longer follow it and their share moves onto the hero.
+    */
+    pub fn attenuation(&self, next: &Self, color: Color) -> Color {
+        let mut attenuation = self.upsample(color);
+        if next.collapsed && !self.collapsed {
+            attenuation[0] *= WAVELENGTH_COUNT as f32;
+            for i in 1..WAVELENGTH_COUNT {
+                attenuation[i] = 0.;
+            }
+        }
+        attenuation
+    }
+
+    /// Linear sRGB of the spectral radiance estimated by this path, white balanced so daylight is white
+    pub fn to_rgb(&self, radiance: Color) -> Color {
+        let mut xyz = [0.; 3];
+        for (i, &wavelength) in self.lambda.iter().enumerate() {
+            let matching = color_matching(wavelength);
+            for channel in 0..3 {
+                xyz[channel] += radiance[i] * matching[channel];
+            }
+        }
+
+        let rgb = xyz_to_linear_srgb(xyz);
+        let white = white_point();
+        Color::new(
+            rgb[0] / white[0],
+            rgb[1] / white[1],
+            rgb[2] / white[2],
+            radiance.w(),
+        )
+    }
+}
+
+/// Linear sRGB of a spectral radiance (eg: a blackbody), integrated with `samples` stratified wavelengths.
+/// Out of gamut colors are clipped.
+pub fn spectrum_to_rgb(spectrum: impl Fn(f32) -> f32, samples: usize) -> Color {
+    let mut xyz = [0.; 3];
+    for sample in 0..samples {
+        let wavelength =
+            LAMBDA_MIN + (sample as f32 + 0.5) / samples as f32 * (LAMBDA_MAX - LAMBDA_MIN);
+        let value = spectrum(wavelength);
+        let matching = color_matching(wavelength);
+        for channel in 0..3 {
+            xyz[channel] += value * matching[channel] * WAVELENGTH_COUNT as f32 / samples as f32;
+        }
+    }
+
+    xyz_to_rgb(xyz)
+}
+
+/// Linear sRGB of a spectral reflectance (eg: of a thin film) under white light, see [`spectrum_to_rgb`]
+pub fn reflectance_to_rgb(reflectance: impl Fn(f32) -> f32, samples: usize) -> Color {
+    spectrum_to_rgb(
+        |wavelength| reflectance(wavelength) * white_light(wavelength),
+        samples,
+    )
+}
+
+/// Linear sRGB of a CIE XYZ color (eg: from a sky model) given in photometric units, Y in cd / m².
+/// Same units as [`spectrum_to_rgb`], out of gamut colors are clipped.
+pub fn photometric_xyz_to_rgb(xyz: [f32; 3]) -> Color {
+    // Y of a spectrum is LUMINOUS_EFFICACY times its integral against the matching function, while
+    // spectrum_to_rgb averages over the visible range
+    let scale = WAVELENGTH_COUNT as f32 / (LUMINOUS_EFFICACY * (LAMBDA_MAX - LAMBDA_MIN));
+    xyz_to_rgb([xyz[0] * scale, xyz[1] * scale, xyz[2] * scale])
+}
+
+/// White balanced linear sRGB, alpha 1
+fn xyz_to_rgb(xyz: [f32; 3]) -> Color {
+    let rgb = xyz_to_linear_srgb(xyz);
+    let white = white_point();
+    Color::new(
+        (rgb[0] / white[0]).max(0.),
+        (rgb[1] / white[1]).max(0.),
+        (rgb[2] / white[2]).max(0.),
+        1.,
+    )
+}
+
+/// Planck's law, spectral radiance of a blackbody at `temperature` (in K) in W / (sr m² nm)
+pub fn blackbody(wavelength: f32, temperature: f32) -> f32 {
+    const PLANCK: f64 = 6.626_070_15e-34;
+    const SPEED_OF_LIGHT: f64 = 299_792_458.;
+    const BOLTZMANN: f64 = 1.380_649e-23;
+
+    // f64, the intermediate values are way out of the range of f32
+    let lambda = wavelength as f64 * 1e-9;
+    let radiance = 2. * PLANCK * SPEED_OF_LIGHT * SPEED_OF_LIGHT /
+        (lambda.powi(5) *
+            ((PLANCK * SPEED_OF_LIGHT / (lambda * BOLTZMANN * temperature as f64)).exp() - 1.));
+    (radiance * 1e-9) as f32 // per m => per nm
+}
+
+/// Spectrum of white light, normalised to 1 at 560 nm
+fn white_light(wavelength: f32) -> f32 {
+    blackbody(wavelength, WHITE_TEMPERATURE) / blackbody(560., WHITE_TEMPERATURE)
+}
+
+/// Smits' RGB to spectrum conversion, evaluated at a single wavelength
+fn rgb_to_spectrum(color: Color, wavelength: f32) -> f32 {
+    let (r, g, b) = (color.x(), color.y(), color.z());
+    let basis = |spectrum: &[f32; 10]| sample_bins(spectrum, wavelength);
+
+    if r <= g && r <= b {
+        let mut value = r * basis(&WHITE_SPECTRUM);
+        if g <= b {
+            value += (g - r) * basis(&CYAN_SPECTRUM) + (b - g) * basis(&BLUE_SPECTRUM);
+        } else {
+            value += (b - r) * basis(&CYAN_SPECTRUM) + (g - b) * basis(&GREEN_SPECTRUM);
+        }
+        value
+    } else if g <= r && g <= b {
+        let mut value = g * basis(&WHITE_SPECTRUM);
+        if r <= b {
+            value += (r - g) * basis(&MAGENTA_SPECTRUM) + (b - r) * basis(&BLUE_SPECTRUM);
+        } else {
+            value += (b - g) * basis(&MAGENTA_SPECTRUM) + (r - b) * basis(&RED_SPECTRUM);
+        }
+        value
+    } else {
+        let mut value = b * basis(&WHITE_SPECTRUM);
+        if r <= g {
+            value += (r - b) * basis(&YELLOW_SPECTRUM) + (g - r) * basis(&GREEN_SPECTRUM);
+        } else {
+            value += (g - b) * basis(&YELLOW_SPECTRUM) + (r - g) * basis(&RED_SPECTRUM);
+        }
+        value
+    }
+}
+
+/// Linearly interpolates between bin centres
+fn sample_bins(bins: &[f32; 10], wavelength: f32) -> f32 {
+    let bin_width = (LAMBDA_MAX - LAMBDA_MIN) / bins.len() as f32;
+    let position = ((wavelength - LAMBDA_MIN) / bin_width - 0.5).clamp(0., (bins.len() - 1) as f32);
+    let index = (position as usize).min(bins.len() - 2);
+    let t = position - index as f32;
+
+    bins[index] * (1. - t) + bins[index + 1] * t
+}
+
+/// CIE 1931 2° colour matching functions, using the multi-lobe gaussian fit by Wyman et al.
+pub fn color_matching(wavelength: f32) -> [f32; 3] {
+    let lobe = |mean: f32, sigma_low: f32, sigma_high: f32| {
+        let sigma = if wavelength < mean {
+            sigma_low
+        } else {
+            sigma_high
+        };
+        (-0.5 * ((wavelength - mean) / sigma).powi(2)).exp()
+    };
+
+    [
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) -
+            0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    ]
+}
+
+fn xyz_to_linear_srgb(xyz: [f32; 3]) -> [f32; 3] {
+    [
+        3.2406 * xyz[0] - 1.5372 * xyz[1] - 0.4986 * xyz[2],
+        -0.9689 * xyz[0] + 1.8758 * xyz[1] + 0.0415 * xyz[2],
+        0.0557 * xyz[0] - 0.2040 * xyz[1] + 1.0570 * xyz[2],
+    ]
+}
+
+/// Expected linear sRGB of a path carrying white light
+fn white_point() -> &'static [f32; 3] {
+    static WHITE_POINT: OnceLock<[f32; 3]> = OnceLock::new();
+    WHITE_POINT.get_or_init(|| {
+        const STEPS: usize = 1000;
+        let mut xyz = [0.; 3];
+        for step in 0..STEPS {
+            let wavelength =
+                LAMBDA_MIN + (step as f32 + 0.5) / STEPS as f32 * (LAMBDA_MAX - LAMBDA_MIN);
+            let matching = color_matching(wavelength);
+            let white = white_light(wavelength);
+            for channel in 0..3 {
+                // each of the carried wavelengths contributes its matching value
+                xyz[channel] += white * matching[channel] * WAVELENGTH_COUNT as f32 / STEPS as f32;
+            }
+        }
+        xyz_to_linear_srgb(xyz)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_COUNT: usize = 1000;
+
+    /// Average RGB estimated by paths carrying `spectrum`, over stratified hero wavelengths
+    fn estimate_rgb(spectrum: impl Fn(&Wavelengths) -> Color) -> Color {
+        let mut rgb = Color::new(0., 0., 0., 0.);
+        for sample in 0..SAMPLE_COUNT {
+            let wavelengths = Wavelengths::sample((sample as f32 + 0.5) / SAMPLE_COUNT as f32);
+            rgb += wavelengths.to_rgb(spectrum(&wavelengths)) / SAMPLE_COUNT as f32;
+        }
+        rgb
+    }
+
+    fn assert_close(actual: Color, expected: Color, tolerance: f32) {
+        for channel in 0..3 {
+            assert!(
+                (actual[channel] - expected[channel]).abs() < tolerance,
+                "channel {channel}: {} instead of {}",
+                actual[channel],
+                expected[channel]
+            );
+        }
+    }
+
+    #[test]
+    fn upsampled_colors_reproject_to_themselves() {
+        let white = Color::new(1., 1., 1., 1.);
+        for color in [
+            white,
+            Color::new(1., 0., 0., 1.),
+            Color::new(0., 1., 0., 1.),
+            Color::new(0., 0., 1., 1.),
+            Color::new(0.8, 0.4, 0.1, 1.),
+        ] {
+            // Smits' spectra are smooth, the primaries come back slightly desaturated
+            assert_close(
+                estimate_rgb(|wavelengths| wavelengths.upsample_light(color)),
+                color,
+                0.05,
+            );
+            assert_close(
+                estimate_rgb(|wavelengths| {
+                    wavelengths.upsample(color) * wavelengths.upsample_light(white)
+                }),
+                color,
+                0.05,
+            );
+            assert_close(
+                reflectance_to_rgb(
+                    |wavelength| rgb_to_spectrum(color, wavelength),
+                    100,
+                ),
+                color,
+                0.05,
+            );
+        }
+    }
+
+    #[test]
+    fn daylight_is_white() {
+        let white = Color::new(1., 1., 1., 1.);
+        assert_close(
+            estimate_rgb(|wavelengths| wavelengths.evaluate(white_light)),
+            white,
+            1e-3,
+        );
+        assert_close(spectrum_to_rgb(white_light, 100), white, 1e-3);
+        assert_close(reflectance_to_rgb(|_| 1., 100), white, 1e-3);
+    }
+
+    #[test]
+    fn color_matching_fits_the_cie_tables() {
+        for (wavelength, expected) in [
+            (445., [0.3481, 0.0298, 1.7826]),
+            (450., [0.3362, 0.0380, 1.7721]),
+            (555., [0.5121, 1.0000, 0.0057]),
+            (600., [1.0622, 0.6310, 0.0008]),
+        ] {
+            let matching = color_matching(wavelength);
+            for channel in 0..3 {
+                assert!(
+                    (matching[channel] - expected[channel]).abs() < 0.02,
+                    "{wavelength} nm: {matching:?} instead of {expected:?}"
+                );
+            }
+        }
+    }
+}
@@ -1,13 +1,40 @@
+use super::{medium::MediumStack, spectrum::Wavelengths};
 use crate::utils::vec4::Point;
 
+#[derive(Clone)]
 pub struct Ray {
     pub origin: Point,
     pub direction: Point,
+    pub media: MediumStack, // nested dielectrics the ray is inside of
+    pub wavelengths: Option<Wavelengths>, // only set for spectral renders
+    // density the direction was sampled with, to weigh lights it hits (MIS), None or 0 if discrete
+    pub scattering_pdf: Option<f32>,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Point) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            media: MediumStack::default(),
+            wavelengths: None,
+            scattering_pdf: None,
+        }
+    }
+
+    pub fn with_media(mut self, media: MediumStack) -> Self {
+        self.media = media;
+        self
+    }
+
+    pub fn with_wavelengths(mut self, wavelengths: Option<Wavelengths>) -> Self {
+        self.wavelengths = wavelengths;
+        self
+    }
+
+    pub fn with_scattering_pdf(mut self, scattering_pdf: Option<f32>) -> Self {
+        self.scattering_pdf = scattering_pdf;
+        self
     }
 
     pub fn at(&self, t: f32) -> Point {
@@ -1,15 +1,59 @@
-use crate::ray_tracer::interface::object_base::Object;
+use crate::environments::{gradient_environment::GradientEnvironment, sky::Sky};
+use crate::lights::sphere_light::SphereLight;
+use crate::objects::sphere::Sphere;
+use crate::ray_tracer::interface::{
+    environment_base::Environment, light_base::Light, object_base::Object,
+};
 
 pub struct Scene {
     pub objects: Vec<Box<dyn Object>>,
+    pub lights: Vec<Box<dyn Light>>, // lit through shadow rays (direct lighting)
+    pub environment: Box<dyn Environment>, // seen by rays that miss every object, also lights the scene
+    pub transparent_background: bool, // camera rays that miss are transparent, the environment still lights
 }
 
 impl Scene {
     pub fn new() -> Self {
-        Scene { objects: vec![] }
+        Scene {
+            objects: vec![],
+            lights: vec![],
+            environment: Box::new(GradientEnvironment::default()),
+            transparent_background: false,
+        }
     }
 
     pub fn add(&mut self, obj: Box<dyn Object>) {
         self.objects.push(obj);
     }
+
+    pub fn add_light(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    /// Emissive sphere that also lights the scene through shadow rays, see [`SphereLight`]
+    pub fn add_sphere_light(&mut self, sphere: Sphere) {
+        self.add_light(Box::new(SphereLight::new(&sphere)));
+        self.add(Box::new(sphere));
+    }
+
+    pub fn set_environment(&mut self, environment: Box<dyn Environment>) {
+        self.environment = environment;
+    }
+
+    /// Daylight, the sky as the environment and the sun as a light
+    pub fn set_sky(&mut self, sky: Sky) {
+        self.add_light(Box::new(sky.sun()));
+        self.set_environment(Box::new(sky));
+    }
+
+    /// For compositing, the background gets alpha 0 while reflections and lighting are unchanged
+    pub fn set_transparent_background(&mut self, transparent_background: bool) {
+        self.transparent_background = transparent_background;
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -0,0 +1,55 @@
+use crate::ray_tracer::interface::texture_base::Texture;
+use crate::utils::vec4::{Color, Point};
+use std::rc::Rc;
+
+enum CheckerMapping {
+    /// 3D checker in world space, the value is the size of a single cell
+    Solid(f32),
+    /// checker on the surface co-ordinates, the values are the cell counts along u and v
+    Uv(f32, f32),
+}
+
+pub struct CheckerTexture {
+    even: Rc<dyn Texture>,
+    odd: Rc<dyn Texture>,
+    mapping: CheckerMapping,
+}
+
+impl CheckerTexture {
+    /// Checker made of cubes of `cell_size` in world space, does not need surface co-ordinates
+    pub fn new(even: Rc<dyn Texture>, odd: Rc<dyn Texture>, cell_size: f32) -> Self {
+        Self {
+            even,
+            odd,
+            mapping: CheckerMapping::Solid(cell_size),
+        }
+    }
+
+    /// Checker with `u_count` x `v_count` cells over the surface co-ordinates
+    pub fn uv(even: Rc<dyn Texture>, odd: Rc<dyn Texture>, u_count: f32, v_count: f32) -> Self {
+        Self {
+            even,
+            odd,
+            mapping: CheckerMapping::Uv(u_count, v_count),
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f32, v: f32, point: &Point) -> Color {
+        let cell_sum = match self.mapping {
+            CheckerMapping::Solid(cell_size) => {
+                (point.x() / cell_size).floor() +
+                    (point.y() / cell_size).floor() +
+                    (point.z() / cell_size).floor()
+            }
+            CheckerMapping::Uv(u_count, v_count) => (u * u_count).floor() + (v * v_count).floor(),
+        };
+
+        if cell_sum as i64 % 2 == 0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::ray_tracer::interface::texture_base::Texture;
+use crate::utils::image::Image;
+use crate::utils::vec4::{Color, Point};
+
+/// What happens to surface co-ordinates outside of [0, 1]
+#[derive(Clone, Copy)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Clone, Copy)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+}
+
+pub struct ImageTexture {
+    image: Image,
+    wrap_mode: WrapMode,
+    filter_mode: FilterMode,
+}
+
+impl ImageTexture {
+    /// Expects the pixels to be gamma corrected (gamma = 2, same as the output of the engine)
+    pub fn new(image: Image) -> Self {
+        let mut image = image;
+        // bring the pixels back to linear space, alpha is already linear
+        for pixel in image.pixels.iter_mut() {
+            *pixel = Color::new(
+                pixel.x().powi(2),
+                pixel.y().powi(2),
+                pixel.z().powi(2),
+                pixel.w(),
+            );
+        }
+
+        Self::new_linear(image)
+    }
+
+    /// Uses the pixels as they are, for data that is not a color (eg: normal maps, height maps)
+    pub fn new_linear(image: Image) -> Self {
+        Self {
+            image,
+            wrap_mode: WrapMode::Repeat,
+            filter_mode: FilterMode::Bilinear,
+        }
+    }
+
+    /// Loads a PNG or PPM file
+    pub fn load(path: &str) -> Result<Self, String> {
+        Ok(Self::new(Image::load(path)?))
+    }
+
+    /// Loads a PNG or PPM file without gamma correction, see [`ImageTexture::new_linear`]
+    pub fn load_linear(path: &str) -> Result<Self, String> {
+        Ok(Self::new_linear(Image::load(path)?))
+    }
+
+    pub fn with_wrap_mode(mut self, wrap_mode: WrapMode) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
+
+    pub fn with_filter_mode(mut self, filter_mode: FilterMode) -> Self {
+        self.filter_mode = filter_mode;
+        self
+    }
+
+    /// Maps a texel index that might be out of the image back onto it
+    fn wrap(&self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self.wrap_mode {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Clamp => index.clamp(0, size - 1),
+            WrapMode::Mirror => {
+                let period = index.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+        };
+        wrapped as usize
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        self.image.pixel(
+            self.wrap(x, self.image.width),
+            self.wrap(y, self.image.height),
+        )
+    }
+}
+
+impl Texture for ImageTexture {
+    /// Empty images have no texels to look up and are black
+    fn value(&self, u: f32, v: f32, _point: &Point) -> Color {
+        if self.image.width == 0 || self.image.height == 0 {
+            return Color::new(0., 0., 0., 1.);
+        }
+
+        // image rows start from the top, v starts from the bottom
+        let x = u * self.image.width as f32;
+        let y = (1. - v) * self.image.height as f32;
+
+        match self.filter_mode {
+            FilterMode::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            FilterMode::Bilinear => {
+                // texel centers are at half integers
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1. - tx) + self.texel(x0 + 1, y0) * tx;
+                let bottom = self.texel(x0, y0 + 1) * (1. - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+                top * (1. - ty) + bottom * ty
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4 x 1 image with the texel index in the red channel
+    fn ramp(wrap_mode: WrapMode) -> ImageTexture {
+        ImageTexture::new_linear(Image {
+            width: 4,
+            height: 1,
+            pixels: (0..4).map(|x| Color::new(x as f32, 0., 0., 1.)).collect(),
+        })
+        .with_wrap_mode(wrap_mode)
+        .with_filter_mode(FilterMode::Nearest)
+    }
+
+    fn texels(texture: &ImageTexture, indices: std::ops::Range<i64>) -> Vec<usize> {
+        indices.map(|x| texture.wrap(x, 4)).collect()
+    }
+
+    #[test]
+    fn wraps_texel_indices() {
+        assert_eq!(
+            texels(&ramp(WrapMode::Repeat), -5..6),
+            [3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1]
+        );
+        assert_eq!(
+            texels(&ramp(WrapMode::Clamp), -5..6),
+            [0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3]
+        );
+        assert_eq!(
+            texels(&ramp(WrapMode::Mirror), -5..6),
+            [3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2]
+        );
+    }
+
+    #[test]
+    fn wraps_surface_coordinates() {
+        let point = Point::new(0., 0., 0., 0.);
+        let red = |texture: &ImageTexture, u: f32| texture.value(u, 0.5, &point).x();
+
+        assert_eq!(red(&ramp(WrapMode::Repeat), 1.1), 0.);
+        assert_eq!(red(&ramp(WrapMode::Repeat), -0.1), 3.);
+        assert_eq!(red(&ramp(WrapMode::Clamp), 1.1), 3.);
+        assert_eq!(red(&ramp(WrapMode::Clamp), -0.1), 0.);
+        assert_eq!(red(&ramp(WrapMode::Mirror), 1.1), 3.);
+        assert_eq!(red(&ramp(WrapMode::Mirror), -0.1), 0.);
+    }
+
+    #[test]
+    fn empty_image_is_black() {
+        let point = Point::new(0., 0., 0., 0.);
+        for filter_mode in [FilterMode::Nearest, FilterMode::Bilinear] {
+            let texture = ImageTexture::new(Image {
+                width: 0,
+                height: 0,
+                pixels: vec![],
+            })
+            .with_filter_mode(filter_mode);
+            assert_eq!(
+                texture.value(0.5, 0.5, &point).e,
+                [0., 0., 0., 1.]
+            );
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod checker_texture;
+pub mod image_texture;
+pub mod noise;
+pub mod noise_texture;
+pub mod normal_map;
+pub mod solid_color;
\ No newline at end of file
@@ -0,0 +1,228 @@
+use crate::utils::vec4::Point;
+
+const PERMUTATION_SIZE: usize = 256;
+
+// gradients point to the edges of a cube, as in improved Perlin noise
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1., 1., 0.],
+    [-1., 1., 0.],
+    [1., -1., 0.],
+    [-1., -1., 0.],
+    [1., 0., 1.],
+    [-1., 0., 1.],
+    [1., 0., -1.],
+    [-1., 0., -1.],
+    [0., 1., 1.],
+    [0., -1., 1.],
+    [0., 1., -1.],
+    [0., -1., -1.],
+];
+
+/**
+Next value of a splitmix64 generator. Noise is seeded with it rather than with a `rand` generator,
+whose streams may change between versions and platforms, so a seed always gives the same noise.
+*/
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut value = *state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// Source of a scalar noise value for a point in 3D
+pub trait Noise {
+    /// Noise value at `point`, roughly in [-1, 1]
+    fn sample(&self, point: Point) -> f32;
+}
+
+/// Gradient (Perlin) noise
+pub struct Perlin {
+    permutation: Vec<usize>,
+}
+
+impl Perlin {
+    /// The same seed always gives the same noise
+    pub fn new(seed: u64) -> Self {
+        // Fisher-Yates shuffle
+        let mut state = seed;
+        let mut permutation: Vec<usize> = (0..PERMUTATION_SIZE).collect();
+        for i in (1..PERMUTATION_SIZE).rev() {
+            let j = split_mix(&mut state) % (i as u64 + 1);
+            permutation.swap(i, j as usize);
+        }
+        // doubled so that lookups of hash + offset never overflow
+        permutation.extend_from_within(..);
+
+        Self { permutation }
+    }
+
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let wrap = |value: i64| value.rem_euclid(PERMUTATION_SIZE as i64) as usize;
+        self.permutation[self.permutation[self.permutation[wrap(x)] + wrap(y)] + wrap(z)]
+    }
+
+    fn gradient(&self, x: i64, y: i64, z: i64, dx: f32, dy: f32, dz: f32) -> f32 {
+        let gradient = GRADIENTS[self.hash(x, y, z) % GRADIENTS.len()];
+        gradient[0] * dx + gradient[1] * dy + gradient[2] * dz
+    }
+}
+
+impl Noise for Perlin {
+    fn sample(&self, point: Point) -> f32 {
+        let (xf, yf, zf) = (
+            point.x().floor(),
+            point.y().floor(),
+            point.z().floor(),
+        );
+        let (x, y, z) = (xf as i64, yf as i64, zf as i64);
+        let (dx, dy, dz) = (point.x() - xf, point.y() - yf, point.z() - zf);
+
+        // quintic fade curve, smooth up to the second derivative
+        let fade = |t: f32| t * t * t * (t * (t * 6. - 15.) + 10.);
+        let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+        let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+
+        let corner = |i: i64, j: i64, k: i64| {
+            self.gradient(
+                x + i,
+                y + j,
+                z + k,
+                dx - i as f32,
+                dy - j as f32,
+                dz - k as f32,
+            )
+        };
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                v,
+                lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+}
+
+/// Cellular (Worley) noise, based on the distance to the closest of randomly scattered feature points
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    /// The same seed always gives the same noise
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Feature point of a cell, in [0, 1) relative to the cell corner
+    fn feature_point(&self, x: i64, y: i64, z: i64) -> [f32; 3] {
+        // integer hash of the cell co-ordinates
+        let mut hash = self.seed ^
+            (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^
+            (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F) ^
+            (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        let mut next = || (split_mix(&mut hash) >> 40) as f32 / (1u64 << 24) as f32;
+
+        [next(), next(), next()]
+    }
+
+    /// Distance to the closest feature point, in [0, ~1]
+    pub fn distance(&self, point: Point) -> f32 {
+        let (x, y, z) = (
+            point.x().floor() as i64,
+            point.y().floor() as i64,
+            point.z().floor() as i64,
+        );
+
+        let mut closest = f32::INFINITY;
+        for i in -1..=1 {
+            for j in -1..=1 {
+                for k in -1..=1 {
+                    let feature = self.feature_point(x + i, y + j, z + k);
+                    let dx = (x + i) as f32 + feature[0] - point.x();
+                    let dy = (y + j) as f32 + feature[1] - point.y();
+                    let dz = (z + k) as f32 + feature[2] - point.z();
+                    closest = closest.min(dx * dx + dy * dy + dz * dz);
+                }
+            }
+        }
+
+        closest.sqrt()
+    }
+}
+
+impl Noise for Worley {
+    fn sample(&self, point: Point) -> f32 {
+        // remap distance to the same range as the other noises
+        self.distance(point) * 2. - 1.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 7;
+
+    fn points() -> [Point; 4] {
+        [
+            Point::new(0.3, 0.7, 0.2, 0.),
+            Point::new(1.25, -3.7, 0.1, 0.),
+            Point::new(-12.3, 4.56, 7.89, 0.),
+            Point::new(100.1, 0.2, -50.3, 0.),
+        ]
+    }
+
+    /// Values for `SEED` at `points()`, these only change if the noise itself changes
+    #[test]
+    fn noise_is_pinned_for_a_seed() {
+        let perlin = Perlin::new(SEED);
+        let worley = Worley::new(SEED);
+        for (point, (expected_perlin, expected_worley)) in points().into_iter().zip([
+            (0.2696746, 0.23968995),
+            (-0.42928633, 0.35057902),
+            (-0.22669697, 0.3595463),
+            (-0.5328755, -0.25892067),
+        ]) {
+            assert!((perlin.sample(point) - expected_perlin).abs() < 1e-5);
+            assert!((worley.sample(point) - expected_worley).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn seeds_give_different_noise() {
+        let differs = |a: &dyn Noise, b: &dyn Noise| {
+            points()
+                .into_iter()
+                .any(|point| a.sample(point) != b.sample(point))
+        };
+        assert!(differs(
+            &Perlin::new(SEED),
+            &Perlin::new(SEED + 1)
+        ));
+        assert!(differs(
+            &Worley::new(SEED),
+            &Worley::new(SEED + 1)
+        ));
+    }
+
+    /// Gradient noise is 0 at the lattice points, whatever the gradients are
+    #[test]
+    fn perlin_is_zero_on_the_lattice() {
+        let perlin = Perlin::new(SEED);
+        for point in [
+            Point::new(0., 0., 0., 0.),
+            Point::new(3., -2., 5., 0.),
+            Point::new(-300., 41., 7., 0.),
+        ] {
+            assert!(perlin.sample(point).abs() < 1e-6);
+        }
+    }
+}
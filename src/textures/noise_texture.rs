@@ -0,0 +1,192 @@
+use super::noise::Noise;
+use crate::ray_tracer::interface::texture_base::Texture;
+use crate::utils::vec4::{Color, Point};
+use std::rc::Rc;
+
+/// How octaves of the base noise are combined
+#[derive(Clone, Copy)]
+pub enum Fractal {
+    /// a single octave of the base noise
+    Single,
+    /// fractal Brownian motion, sum of octaves
+    Fbm {
+        octaves: u32,
+        lacunarity: f32, // frequency multiplier between octaves
+        gain: f32,       // amplitude multiplier between octaves
+    },
+    /// sum of the absolute value of octaves, gives sharp creases (fire, marble veins)
+    Turbulence {
+        octaves: u32,
+        lacunarity: f32,
+        gain: f32,
+    },
+}
+
+// Samples the noise in 3D at the hit point and blends between two textures with it.
+// The noise value is mapped to [0, 1], 0 gives `low` and 1 gives `high`.
+pub struct NoiseTexture {
+    noise: Box<dyn Noise>,
+    fractal: Fractal,
+    scale: f32, // frequency of the noise in world space
+    warp: f32,  // strength of domain warping, 0 disables it
+    low: Rc<dyn Texture>,
+    high: Rc<dyn Texture>,
+}
+
+impl NoiseTexture {
+    pub fn new(
+        noise: Box<dyn Noise>,
+        fractal: Fractal,
+        scale: f32,
+        low: Rc<dyn Texture>,
+        high: Rc<dyn Texture>,
+    ) -> Self {
+        Self {
+            noise,
+            fractal,
+            scale,
+            warp: 0.,
+            low,
+            high,
+        }
+    }
+
+    /// Offsets the lookup point by the noise itself, gives swirly, marble and cloud like patterns
+    pub fn with_warp(mut self, warp: f32) -> Self {
+        self.warp = warp;
+        self
+    }
+
+    /// Fractal noise value at `point`, in [-1, 1] (turbulence is in [0, 1])
+    fn fractal_sample(&self, point: Point) -> f32 {
+        let (octaves, lacunarity, gain, absolute) = match self.fractal {
+            Fractal::Single => return self.noise.sample(point),
+            Fractal::Fbm {
+                octaves,
+                lacunarity,
+                gain,
+            } => (octaves, lacunarity, gain, false),
+            Fractal::Turbulence {
+                octaves,
+                lacunarity,
+                gain,
+            } => (octaves, lacunarity, gain, true),
+        };
+
+        let mut sum = 0.;
+        let mut amplitude = 1.;
+        let mut total_amplitude = 0.;
+        let mut frequency = 1.;
+        for _ in 0..octaves {
+            let value = self.noise.sample(point * frequency);
+            sum += amplitude * if absolute { value.abs() } else { value };
+            total_amplitude += amplitude;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+
+        if total_amplitude > 0. {
+            sum / total_amplitude
+        } else {
+            0.
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, u: f32, v: f32, point: &Point) -> Color {
+        let mut p = *point * self.scale;
+        p[3] = 0.;
+
+        if self.warp != 0. {
+            // arbitrary offsets decorrelate the three warp axes
+            let offset = Point::new(
+                self.fractal_sample(p + Point::new(5.2, 1.3, 7.1, 0.)),
+                self.fractal_sample(p + Point::new(1.7, 9.2, 3.4, 0.)),
+                self.fractal_sample(p + Point::new(8.3, 2.8, 4.6, 0.)),
+                0.,
+            );
+            p += offset * self.warp;
+        }
+
+        let value = self.fractal_sample(p);
+        let t = match self.fractal {
+            Fractal::Turbulence { .. } => value,
+            _ => value * 0.5 + 0.5,
+        }
+        .clamp(0., 1.);
+
+        self.low.value(u, v, point) * (1. - t) + self.high.value(u, v, point) * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textures::{noise::Perlin, solid_color::SolidColor};
+
+    fn texture(fractal: Fractal) -> NoiseTexture {
+        NoiseTexture::new(
+            Box::new(Perlin::new(7)),
+            fractal,
+            2.,
+            Rc::new(SolidColor::new(Color::new(0., 0., 0., 1.))),
+            Rc::new(SolidColor::new(Color::new(1., 1., 1., 1.))),
+        )
+    }
+
+    /// Perlin noise is 0 on the lattice, halfway between the two textures
+    #[test]
+    fn lattice_points_blend_halfway() {
+        let color = texture(Fractal::Single).value(0., 0., &Point::new(1.5, -2., 0.5, 0.));
+        for channel in 0..3 {
+            assert!(
+                (color[channel] - 0.5).abs() < 1e-5,
+                "{:?}",
+                color.e
+            );
+        }
+    }
+
+    #[test]
+    fn single_octave_fbm_is_the_base_noise() {
+        let single = texture(Fractal::Single);
+        let fbm = texture(Fractal::Fbm {
+            octaves: 1,
+            lacunarity: 2.,
+            gain: 0.5,
+        });
+        for point in [
+            Point::new(0.3, 0.7, 0.2, 0.),
+            Point::new(-4.1, 2.6, 9.9, 0.),
+        ] {
+            let (a, b) = (
+                single.value(0., 0., &point),
+                fbm.value(0., 0., &point),
+            );
+            assert!((a - b).length() < 1e-6);
+        }
+    }
+
+    /// Blends stay between the two textures, including turbulence and warping
+    #[test]
+    fn values_stay_between_low_and_high() {
+        let fractal = Fractal::Turbulence {
+            octaves: 4,
+            lacunarity: 2.,
+            gain: 0.5,
+        };
+        for texture in [texture(fractal), texture(fractal).with_warp(1.5)] {
+            for i in 0..100 {
+                let point = Point::new(
+                    i as f32 * 0.37,
+                    i as f32 * -0.21,
+                    i as f32 * 0.13,
+                    0.,
+                );
+                let color = texture.value(0., 0., &point);
+                assert!((0. ..=1.).contains(&color.x()), "{:?}", color.e);
+            }
+        }
+    }
+}
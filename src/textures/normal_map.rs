@@ -0,0 +1,162 @@
+use crate::ray_tracer::interface::{
+    normal_map_base::NormalMap, object_base::HitRecord, texture_base::Texture,
+};
+use crate::utils::{frame::Frame, vec4::Vec4};
+use std::rc::Rc;
+
+// step in surface co-ordinates used for the finite differences of the bump map
+const BUMP_DELTA: f32 = 0.0005;
+
+/// Keeps the shading normal on the same side of the surface as the geometric normal
+fn same_side(normal: Vec4, reference: Vec4) -> Vec4 {
+    if normal.dot(reference) < 0. {
+        -normal
+    } else {
+        normal
+    }
+}
+
+// Normal map in tangent space, r => tangent, g => bitangent, b => normal (OpenGL convention).
+// The texture must hold raw values, see ImageTexture::new_linear.
+pub struct TangentSpaceNormalMap {
+    texture: Rc<dyn Texture>,
+    strength: f32, // scales the tangent components, 0 disables the map
+}
+
+impl TangentSpaceNormalMap {
+    pub fn new(texture: Rc<dyn Texture>, strength: f32) -> Self {
+        Self { texture, strength }
+    }
+}
+
+impl NormalMap for TangentSpaceNormalMap {
+    fn perturb(&self, hit_record: &HitRecord) -> Vec4 {
+        let Frame {
+            tangent,
+            bitangent,
+            normal,
+        } = hit_record.tangent_frame();
+
+        // [0, 1] => [-1, 1]
+        let value = self.texture.value(
+            hit_record.u,
+            hit_record.v,
+            &hit_record.point_of_intersection,
+        ) * 2.
+            - Vec4::new(1., 1., 1., 1.);
+
+        let perturbed = tangent * (value.x() * self.strength) +
+            bitangent * (value.y() * self.strength) +
+            normal * value.z();
+        if perturbed.is_degenerate() {
+            return hit_record.geometric_normal;
+        }
+
+        same_side(perturbed.normalise(), hit_record.geometric_normal)
+    }
+}
+
+// Bump map from a height texture (red channel), the normal tilts along the slope of the height
+pub struct BumpMap {
+    height: Rc<dyn Texture>,
+    scale: f32, // height in world units of a texture value of 1
+}
+
+impl BumpMap {
+    pub fn new(height: Rc<dyn Texture>, scale: f32) -> Self {
+        Self { height, scale }
+    }
+
+    fn height_at(&self, hit_record: &HitRecord, du: f32, dv: f32) -> f32 {
+        // the point moves as well, for solid textures
+        let point = hit_record.point_of_intersection + hit_record.dpdu * du + hit_record.dpdv * dv;
+        self.height
+            .value(hit_record.u + du, hit_record.v + dv, &point)
+            .x() *
+            self.scale
+    }
+}
+
+impl NormalMap for BumpMap {
+    fn perturb(&self, hit_record: &HitRecord) -> Vec4 {
+        let Frame {
+            tangent,
+            bitangent,
+            normal,
+        } = hit_record.tangent_frame();
+
+        let height = self.height_at(hit_record, 0., 0.);
+        let height_du = (self.height_at(hit_record, BUMP_DELTA, 0.) - height) / BUMP_DELTA;
+        let height_dv = (self.height_at(hit_record, 0., BUMP_DELTA) - height) / BUMP_DELTA;
+
+        // displaced surface derivatives, falling back to the tangent frame where they vanish
+        let dpdu = if hit_record.dpdu.is_degenerate() {
+            tangent
+        } else {
+            hit_record.dpdu
+        };
+        let dpdv = if hit_record.dpdv.is_degenerate() {
+            bitangent
+        } else {
+            hit_record.dpdv
+        };
+        let dpdu = dpdu + normal * height_du;
+        let dpdv = dpdv + normal * height_dv;
+
+        let perturbed = dpdu.cross(dpdv);
+        if perturbed.is_degenerate() {
+            return hit_record.geometric_normal;
+        }
+
+        same_side(perturbed.normalise(), hit_record.geometric_normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::objects::sphere::Sphere;
+    use crate::ray_tracer::{interface::object_base::Object, utils::Ray};
+    use crate::textures::solid_color::SolidColor;
+    use crate::utils::vec4::{Color, Point};
+
+    /// Hit on the side of a sphere, where the tangents are well defined
+    fn hit_record() -> HitRecord {
+        let material = Rc::new(Lambertian::new(Color::new(1., 1., 1., 1.)));
+        let ray = Ray::new(
+            Point::new(0.6, 0.3, 5., 0.),
+            Vec4::new(0., 0., -1., 0.),
+        );
+        Sphere::new(1., Point::new(0., 0., 0., 0.), material)
+            .is_ray_hit(&ray, 0.001, f32::INFINITY)
+            .unwrap()
+    }
+
+    #[test]
+    fn flat_maps_leave_the_normal_unchanged() {
+        let hit_record = hit_record();
+        let flat = Rc::new(SolidColor::new(Color::new(0.5, 0.5, 1., 1.)));
+
+        let normal = TangentSpaceNormalMap::new(flat.clone(), 1.).perturb(&hit_record);
+        assert!((normal - hit_record.normal).length() < 1e-5);
+        let normal = BumpMap::new(flat, 1.).perturb(&hit_record);
+        assert!((normal - hit_record.normal).length() < 1e-4);
+    }
+
+    /// Even when the map tilts the normal as far as it goes, it stays on the geometric side
+    #[test]
+    fn strong_maps_stay_on_the_geometric_side() {
+        let hit_record = hit_record();
+        for value in [
+            Color::new(1., 0.5, 0., 1.),
+            Color::new(0., 0., 0., 1.),
+            Color::new(0.5, 1., 0.5, 1.),
+        ] {
+            let map = TangentSpaceNormalMap::new(Rc::new(SolidColor::new(value)), 10.);
+            let normal = map.perturb(&hit_record);
+            assert!(normal.dot(hit_record.geometric_normal) >= 0.);
+            assert!((normal.length() - 1.).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use crate::ray_tracer::interface::texture_base::Texture;
+use crate::utils::vec4::{Color, Point};
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _point: &Point) -> Color {
+        self.color
+    }
+}
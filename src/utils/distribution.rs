@@ -0,0 +1,206 @@
+/// Piecewise constant distribution over [0, 1), drawn proportional to the function values
+pub struct Distribution1D {
+    function: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    /// `function` values must not be negative, all zeros gives a uniform distribution. None if empty.
+    pub fn new(function: Vec<f32>) -> Option<Self> {
+        if function.is_empty() {
+            return None;
+        }
+
+        let count = function.len() as f32;
+        let mut cdf = vec![0.];
+        for value in &function {
+            cdf.push(cdf[cdf.len() - 1] + value / count);
+        }
+
+        let integral = cdf[cdf.len() - 1];
+        for (i, value) in cdf.iter_mut().enumerate() {
+            *value = if integral > 0. {
+                *value / integral
+            } else {
+                i as f32 / count
+            };
+        }
+
+        Some(Self {
+            function,
+            cdf,
+            integral,
+        })
+    }
+
+    /// Integral of the function over [0, 1)
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    /**
+    Draws a value from the distribution with the inverse of its CDF.
+
+    - `u`: uniform random number in [0, 1)
+
+    Returns:
+
+    The value in [0, 1) and its probability density
+    */
+    pub fn sample(&self, u: f32) -> (f32, f32) {
+        let count = self.function.len();
+        let index = (self.cdf.partition_point(|value| *value <= u).max(1) - 1).min(count - 1);
+
+        // position inside of the segment
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0. {
+            (u - self.cdf[index]) / width
+        } else {
+            0.
+        };
+        let value = ((index as f32 + offset) / count as f32).min(1. - f32::EPSILON);
+
+        (value, self.pdf(value))
+    }
+
+    /**
+    Draws one of the function values, proportional to its size.
+
+    - `u`: uniform random number in [0, 1)
+
+    Returns:
+
+    The index of the value and the probability of drawing it
+    */
+    pub fn sample_discrete(&self, u: f32) -> (usize, f32) {
+        let count = self.function.len();
+        let (value, pdf) = self.sample(u);
+        (
+            ((value * count as f32) as usize).min(count - 1),
+            pdf / count as f32,
+        )
+    }
+
+    /// Probability of `sample_discrete` drawing the value at `index`
+    pub fn discrete_probability(&self, index: usize) -> f32 {
+        let count = self.function.len();
+        self.pdf((index as f32 + 0.5) / count as f32) / count as f32
+    }
+
+    /// Probability density of drawing `value`
+    pub fn pdf(&self, value: f32) -> f32 {
+        if self.integral <= 0. {
+            return 1.;
+        }
+        let count = self.function.len();
+        let index = ((value * count as f32) as usize).min(count - 1);
+        self.function[index] / self.integral
+    }
+}
+
+/**
+Piecewise constant distribution over [0, 1)², drawn proportional to a grid of function values
+(eg: the brightness of the pixels of an image). A row is picked from the marginal distribution, then
+a column from the distribution of that row.
+*/
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `function` is stored row by row, `width` values per row. None if it is empty.
+    pub fn new(function: &[f32], width: usize, height: usize) -> Option<Self> {
+        let rows: Vec<Distribution1D> = (0..height)
+            .map(|y| Distribution1D::new(function[y * width..(y + 1) * width].to_vec()))
+            .collect::<Option<_>>()?;
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect())?;
+
+        Some(Self { rows, marginal })
+    }
+
+    /**
+    Draws a point from the distribution.
+
+    - `u1`, `u2`: uniform random numbers in [0, 1)
+
+    Returns:
+
+    The point (x, y) in [0, 1)² and its probability density
+    */
+    pub fn sample(&self, u1: f32, u2: f32) -> ((f32, f32), f32) {
+        let (y, marginal_pdf) = self.marginal.sample(u2);
+        let (x, conditional_pdf) = self.row(y).sample(u1);
+        ((x, y), marginal_pdf * conditional_pdf)
+    }
+
+    /// Probability density of drawing the point (x, y)
+    pub fn pdf(&self, x: f32, y: f32) -> f32 {
+        self.marginal.pdf(y) * self.row(y).pdf(x)
+    }
+
+    fn row(&self, y: f32) -> &Distribution1D {
+        &self.rows[((y * self.rows.len() as f32) as usize).min(self.rows.len() - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_COUNT: usize = 10_000;
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let function = [1., 3., 0., 2., 4., 0.5];
+        let distribution = Distribution1D::new(function.to_vec()).unwrap();
+        let integral: f32 = (0..SAMPLE_COUNT)
+            .map(|i| distribution.pdf((i as f32 + 0.5) / SAMPLE_COUNT as f32))
+            .sum::<f32>() /
+            SAMPLE_COUNT as f32;
+        assert!((integral - 1.).abs() < 1e-3, "{}", integral);
+
+        let distribution = Distribution2D::new(&function, 3, 2).unwrap();
+        let steps = 120; // whole cells on both axes
+        let mut integral = 0.;
+        for y in 0..steps {
+            for x in 0..steps {
+                let point = |i: usize| (i as f32 + 0.5) / steps as f32;
+                integral += distribution.pdf(point(x), point(y)) / (steps * steps) as f32;
+            }
+        }
+        assert!((integral - 1.).abs() < 1e-3, "{}", integral);
+    }
+
+    #[test]
+    fn discrete_probabilities_match_the_function() {
+        let function = [1., 3., 0., 2., 4.];
+        let total: f32 = function.iter().sum();
+        let distribution = Distribution1D::new(function.to_vec()).unwrap();
+
+        let mut counts = [0; 5];
+        for i in 0..SAMPLE_COUNT {
+            let (index, probability) =
+                distribution.sample_discrete((i as f32 + 0.5) / SAMPLE_COUNT as f32);
+            assert!((probability - function[index] / total).abs() < 1e-6);
+            counts[index] += 1;
+        }
+        for (index, value) in function.iter().enumerate() {
+            let expected = value / total;
+            assert!((distribution.discrete_probability(index) - expected).abs() < 1e-6);
+            let share = counts[index] as f32 / SAMPLE_COUNT as f32;
+            assert!(
+                (share - expected).abs() < 1e-3,
+                "{index}: drawn {share} instead of {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_empty_functions() {
+        assert!(Distribution1D::new(vec![]).is_none());
+        assert!(Distribution2D::new(&[], 0, 2).is_none());
+        assert!(Distribution2D::new(&[], 2, 0).is_none());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::utils::vec4::Vec4;
+
+/// Orthonormal shading frame, local co-ordinates have the normal as +z
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub tangent: Vec4,
+    pub bitangent: Vec4,
+    pub normal: Vec4,
+}
+
+impl Frame {
+    /// Frame with an arbitrary tangent around a unit `normal`
+    pub fn from_normal(normal: Vec4) -> Self {
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Self {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    /// Same frame seen from the other side of the surface, the tangent stays put
+    pub fn flipped(&self) -> Self {
+        Self {
+            tangent: self.tangent,
+            bitangent: -self.bitangent,
+            normal: -self.normal,
+        }
+    }
+
+    /// Frame with the tangent rotated by `angle` (in radians) around the normal, towards the bitangent
+    pub fn rotated(&self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            tangent: self.tangent * cos + self.bitangent * sin,
+            bitangent: self.bitangent * cos - self.tangent * sin,
+            normal: self.normal,
+        }
+    }
+
+    pub fn to_local(&self, w: Vec4) -> Vec4 {
+        Vec4::new(
+            w.dot(self.tangent),
+            w.dot(self.bitangent),
+            w.dot(self.normal),
+            0.,
+        )
+    }
+
+    pub fn to_world(&self, w: Vec4) -> Vec4 {
+        self.tangent * w.x() + self.bitangent * w.y() + self.normal * w.z()
+    }
+}
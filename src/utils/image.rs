@@ -0,0 +1,375 @@
+use crate::utils::vec4::Color;
+use std::fs;
+
+/**
+Raw image data, pixels are stored row by row starting from the top left with channels in [0, 1].
+HDR images hold linear radiance, so their channels can go above 1.
+*/
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    /// Loads a PNG, a PPM (P3 / P6) or a Radiance HDR file, the format is detected from the file contents
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        if bytes.starts_with(b"\x89PNG") {
+            Self::decode_png(&bytes)
+        } else if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+            Self::decode_ppm(&bytes)
+        } else if bytes.starts_with(b"#?RADIANCE") || bytes.starts_with(b"#?RGBE") {
+            Self::decode_hdr(&bytes)
+        } else {
+            Err(String::from("unsupported image format"))
+        }
+        .map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    fn decode_png(bytes: &[u8]) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(bytes);
+        // always get 8 bit channels, palettes expanded
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+
+        let channels = info.color_type.samples();
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|p| {
+                let c = |i: usize| p[i] as f32 / 255.;
+                match channels {
+                    1 => Color::new(c(0), c(0), c(0), 1.),
+                    2 => Color::new(c(0), c(0), c(0), c(1)),
+                    3 => Color::new(c(0), c(1), c(2), 1.),
+                    _ => Color::new(c(0), c(1), c(2), c(3)),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            pixels,
+        })
+    }
+
+    fn decode_ppm(bytes: &[u8]) -> Result<Self, String> {
+        // header: magic, width, height, max value, separated by whitespace, `#` starts a comment
+        let mut position = 0;
+        let mut header = vec![];
+        while header.len() < 4 {
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if position < bytes.len() && bytes[position] == b'#' {
+                while position < bytes.len() && bytes[position] != b'\n' {
+                    position += 1;
+                }
+                continue;
+            }
+
+            let start = position;
+            while position < bytes.len() && !bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if start == position {
+                return Err(String::from("truncated PPM header"));
+            }
+            header.push(String::from_utf8_lossy(&bytes[start..position]).to_string());
+        }
+        position += 1; // single whitespace before the data
+
+        let parse = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|e| format!("invalid PPM header value `{}`: {}", value, e))
+        };
+        let width = parse(&header[1])?;
+        let height = parse(&header[2])?;
+        let max_value = parse(&header[3])? as f32;
+
+        let samples: Vec<f32> = if header[0] == "P6" {
+            let data = bytes.get(position..).unwrap_or_default();
+            if max_value < 256. {
+                data.iter().map(|b| *b as f32).collect()
+            } else {
+                data.chunks_exact(2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32)
+                    .collect()
+            }
+        } else {
+            String::from_utf8_lossy(bytes.get(position..).unwrap_or_default())
+                .split_whitespace()
+                .map(|value| value.parse::<f32>().map_err(|e| e.to_string()))
+                .collect::<Result<_, _>>()?
+        };
+
+        if samples.len() < width * height * 3 {
+            return Err(String::from("not enough PPM pixel data"));
+        }
+
+        let pixels = samples
+            .chunks_exact(3)
+            .take(width * height)
+            .map(|p| {
+                Color::new(
+                    p[0] / max_value,
+                    p[1] / max_value,
+                    p[2] / max_value,
+                    1.,
+                )
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn decode_hdr(bytes: &[u8]) -> Result<Self, String> {
+        // header: text lines ending with an empty line, then the resolution line
+        let mut lines = bytes.split(|b| *b == b'\n');
+        let mut position = 0;
+        loop {
+            let line = lines.next().ok_or("truncated HDR header")?;
+            position += line.len() + 1;
+            if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+                return Err(format!(
+                    "unsupported HDR format `{}`",
+                    String::from_utf8_lossy(line)
+                ));
+            }
+            if line.is_empty() {
+                break;
+            }
+        }
+
+        // only the standard orientation, rows from top to bottom, pixels from left to right
+        let resolution_line = lines.next().ok_or("missing HDR resolution")?;
+        position += resolution_line.len() + 1;
+        let resolution = String::from_utf8_lossy(resolution_line).to_string();
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (height, width),
+            _ => {
+                return Err(format!(
+                    "unsupported HDR resolution `{}`",
+                    resolution
+                ))
+            }
+        };
+        let parse = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|e| format!("invalid HDR resolution `{}`: {}", value, e))
+        };
+        let width = parse(width)?;
+        let height = parse(height)?;
+        if width == 0 || height == 0 {
+            return Err(format!("empty HDR image `{}`", resolution));
+        }
+
+        let data = bytes.get(position..).unwrap_or_default();
+        let mut position = 0;
+        let mut byte = || {
+            let value = data
+                .get(position)
+                .copied()
+                .ok_or("not enough HDR pixel data");
+            position += 1;
+            value
+        };
+
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![[0_u8; 4]; width];
+        for _ in 0..height {
+            let header = [byte()?, byte()?, byte()?, byte()?];
+            let run_length_encoded = (8..32768).contains(&width) &&
+                header[0] == 2 &&
+                header[1] == 2 &&
+                header[2] & 0x80 == 0;
+
+            if run_length_encoded {
+                if (header[2] as usize) << 8 | header[3] as usize != width {
+                    return Err(String::from("HDR scanline width mismatch"));
+                }
+                // each channel separately, as runs of one value or literal bytes
+                for channel in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = byte()? as usize;
+                        let (count, run) = if count > 128 {
+                            (count - 128, true)
+                        } else {
+                            (count, false)
+                        };
+                        if count == 0 || x + count > width {
+                            return Err(String::from("corrupt HDR run length"));
+                        }
+
+                        let value = if run { byte()? } else { 0 };
+                        for rgbe in &mut scanline[x..x + count] {
+                            rgbe[channel] = if run { value } else { byte()? };
+                        }
+                        x += count;
+                    }
+                }
+            } else {
+                // flat RGBE pixels, the header was the first one
+                scanline[0] = header;
+                for rgbe in &mut scanline[1..] {
+                    *rgbe = [byte()?, byte()?, byte()?, byte()?];
+                }
+            }
+
+            // shared exponent: value = mantissa * 2 ^ (exponent - 128 - 8)
+            pixels.extend(scanline.iter().map(|[r, g, b, e]| {
+                if *e == 0 {
+                    return Color::new(0., 0., 0., 1.);
+                }
+                let scale = 2_f32.powi(*e as i32 - 136);
+                Color::new(
+                    *r as f32 * scale,
+                    *g as f32 * scale,
+                    *b as f32 * scale,
+                    1.,
+                )
+            }));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_png(color_type: png::ColorType, width: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let height = data.len() as u32 / (width * color_type.samples() as u32);
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decodes_png() {
+        let rgb = encode_png(png::ColorType::Rgb, 2, &[255, 0, 51, 0, 255, 102]);
+        let image = Image::decode_png(&rgb).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixel(0, 0).e, [1., 0., 0.2, 1.]);
+        assert_eq!(image.pixel(1, 0).e, [0., 1., 0.4, 1.]);
+
+        // gray is copied to every channel, alpha is kept
+        let gray_alpha = encode_png(
+            png::ColorType::GrayscaleAlpha,
+            1,
+            &[51, 255, 255, 0],
+        );
+        let image = Image::decode_png(&gray_alpha).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq!(image.pixel(0, 0).e, [0.2, 0.2, 0.2, 1.]);
+        assert_eq!(image.pixel(0, 1).e, [1., 1., 1., 0.]);
+    }
+
+    #[test]
+    fn decodes_ppm() {
+        let ascii = b"P3\n# comment\n2 1\n255\n255 0 51\n0 255 102\n";
+        let image = Image::decode_ppm(ascii).unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixel(0, 0).e, [1., 0., 0.2, 1.]);
+        assert_eq!(image.pixel(1, 0).e, [0., 1., 0.4, 1.]);
+
+        let mut binary = b"P6 1 2 255\n".to_vec();
+        binary.extend([255, 0, 51, 0, 255, 102]);
+        let image = Image::decode_ppm(&binary).unwrap();
+        assert_eq!((image.width, image.height), (1, 2));
+        assert_eq!(image.pixel(0, 1).e, [0., 1., 0.4, 1.]);
+
+        // 16 bit samples are big endian
+        let mut wide = b"P6 1 1 65535\n".to_vec();
+        wide.extend([255, 255, 0, 0, 128, 0]);
+        let image = Image::decode_ppm(&wide).unwrap();
+        assert_eq!(image.pixel(0, 0).e, [1., 0., 32768. / 65535., 1.]);
+    }
+
+    #[test]
+    fn rejects_truncated_ppm() {
+        assert!(Image::decode_ppm(b"P3 2 1").is_err());
+        assert!(Image::decode_ppm(b"P3 2 1 255 255 0 0").is_err());
+    }
+
+    #[test]
+    fn load_errors_name_the_path_once() {
+        let path = std::env::temp_dir().join("kiroshi_unsupported_image.txt");
+        fs::write(&path, "not an image").unwrap();
+        let path = path.to_str().unwrap();
+
+        let error = Image::load(path).err().unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(
+            error,
+            format!("{}: unsupported image format", path)
+        );
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_hdr() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+        bytes.extend([2, 2, 0, 8]);
+        bytes.extend([128 + 8, 128]); // red, one run
+        bytes.extend([8, 0, 16, 32, 48, 64, 80, 96, 255]); // green, literal values
+        bytes.extend([128 + 8, 0]); // blue
+        bytes.extend([128 + 4, 129, 128 + 4, 130]); // exponents, 2 runs
+
+        let image = Image::decode_hdr(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (8, 1));
+        let expected_green = [0., 0.125, 0.25, 0.375, 1., 1.25, 1.5, 3.984375];
+        for (x, green) in expected_green.iter().enumerate() {
+            let red = if x < 4 { 1. } else { 2. };
+            assert_eq!(image.pixel(x, 0).e, [red, *green, 0., 1.]);
+        }
+    }
+    #[test]
+    fn decodes_flat_hdr() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 2\n".to_vec();
+        bytes.extend([128, 64, 0, 129, 0, 128, 255, 128]);
+        bytes.extend([0, 0, 0, 0, 16, 32, 48, 136]);
+
+        let image = Image::decode_hdr(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixel(0, 0).e, [1., 0.5, 0., 1.]);
+        assert_eq!(image.pixel(1, 0).e, [0., 0.5, 0.99609375, 1.]);
+        assert_eq!(image.pixel(0, 1).e, [0., 0., 0., 1.]);
+        assert_eq!(image.pixel(1, 1).e, [16., 32., 48., 1.]);
+    }
+
+    #[test]
+    fn rejects_empty_hdr() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 0\n".to_vec();
+        bytes.extend([1, 2, 3, 4]);
+        assert!(Image::decode_hdr(&bytes).is_err());
+
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 0 +X 1\n".to_vec();
+        bytes.extend([1, 2, 3, 4]);
+        assert!(Image::decode_hdr(&bytes).is_err());
+    }
+}
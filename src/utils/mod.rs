@@ -1 +1,4 @@
+pub mod distribution;
+pub mod frame;
+pub mod image;
 pub mod vec4;
\ No newline at end of file
@@ -1,7 +1,7 @@
 use rand::prelude::*;
 use std::{
     f32::consts::PI,
-    ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign},
+    ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 const EPSILON: f32 = 1.0e-8;
@@ -88,9 +88,46 @@ impl Vec4 {
         }
     }
 
+    /// Two unit vectors that form an orthonormal basis with this (unit) vector
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        // Duff et al. 2017, "Building an Orthonormal Basis, Revisited"
+        let sign = 1_f32.copysign(self.z());
+        let a = -1. / (sign + self.z());
+        let b = self.x() * self.y() * a;
+
+        (
+            Self::new(
+                1. + sign * self.x() * self.x() * a,
+                sign * b,
+                -sign * self.x(),
+                0.,
+            ),
+            Self::new(b, sign + self.y() * self.y() * a, -self.y(), 0.),
+        )
+    }
+
+    /// Random unit vector around +z with a density proportional to its z component (cosine weighted)
+    pub fn random_cosine_direction() -> Self {
+        let mut rng = rand::thread_rng();
+        let r = rng.gen::<f32>().sqrt();
+        let phi = rng.gen::<f32>() * 2. * PI;
+
+        Self::new(
+            r * phi.cos(),
+            r * phi.sin(),
+            (1. - r * r).max(0.).sqrt(),
+            0.,
+        )
+    }
+
     pub fn is_degenerate(&self) -> bool {
         self.length() < EPSILON
     }
+
+    /// Brightness of a linear RGB color as the eye sees it (Rec. 709 weights)
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
 }
 
 impl Index<usize> for Vec4 {
@@ -151,6 +188,14 @@ impl SubAssign<Self> for Vec4 {
     }
 }
 
+impl Neg for Vec4 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self[0], -self[1], -self[2], -self[3])
+    }
+}
+
 // * Multiplying with another vector (We are doing a dot product here)
 impl Mul<Self> for Vec4 {
     type Output = Self;